@@ -0,0 +1,285 @@
+use crate::mac::MacAddress;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const PROC_NET_ARP: &str = "/proc/net/arp";
+
+// /proc/net/arp flags, an entry without ATF_COM is still being resolved
+const ATF_COM: u32 = 0x02;
+const ATF_PERM: u32 = 0x04;
+
+// An address the local host has resolved to a MAC address on the LAN
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct NeighborEntry {
+    pub ip: IpAddr,
+    pub mac: [u8; 6],
+    pub state: String,
+}
+
+// The IPv4 and IPv6 neighbors of the local host from netlink, or the IPv4 ones from
+// /proc/net/arp where netlink is not available
+pub fn neighbor_table() -> Result<Vec<NeighborEntry>, String> {
+    #[cfg(target_os = "linux")]
+    if let Ok(entries) = netlink::dump_neighbors() {
+        return Ok(entries);
+    }
+
+    let content = std::fs::read_to_string(PROC_NET_ARP)
+        .map_err(|e| format!("Cannot read {}: {}", PROC_NET_ARP, e))?;
+
+    Ok(parse_proc_arp(&content))
+}
+
+// Finds where the local host has seen a MAC address
+pub fn find_mac<'a>(entries: &'a [NeighborEntry], mac: &[u8; 6]) -> Option<&'a NeighborEntry> {
+    entries.iter().find(|entry| entry.mac == *mac)
+}
+
+// IP address, HW type, Flags, HW address, Mask and Device columns after a header line
+fn parse_proc_arp(content: &str) -> Vec<NeighborEntry> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let [ip, _, flags, mac, ..] = columns.as_slice() else {
+                return None;
+            };
+
+            let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+            if flags & ATF_COM == 0 {
+                return None;
+            }
+
+            Some(NeighborEntry {
+                ip: IpAddr::V4(ip.parse::<Ipv4Addr>().ok()?),
+                mac: mac.parse::<MacAddress>().ok()?.octets(),
+                state: if flags & ATF_PERM != 0 {
+                    "permanent".to_string()
+                } else {
+                    "complete".to_string()
+                },
+            })
+        })
+        .collect()
+}
+
+// Neighbor states from linux/neighbour.h
+fn neighbor_state(state: u16) -> Option<&'static str> {
+    match state {
+        0x02 => Some("reachable"),
+        0x04 => Some("stale"),
+        0x08 => Some("delay"),
+        0x10 => Some("probe"),
+        0x40 => Some("noarp"),
+        0x80 => Some("permanent"),
+        // Incomplete and failed entries have no MAC address worth reporting
+        _ => None,
+    }
+}
+
+// The entries of RTM_NEWNEIGH messages in a netlink reply, and whether the dump is done
+fn parse_netlink(buffer: &[u8]) -> Result<(Vec<NeighborEntry>, bool), String> {
+    const NLMSG_ERROR: u16 = 2;
+    const NLMSG_DONE: u16 = 3;
+    const RTM_NEWNEIGH: u16 = 28;
+    const NDA_DST: u16 = 1;
+    const NDA_LLADDR: u16 = 2;
+    const NLMSG_HEADER: usize = 16;
+    const NDMSG: usize = 12;
+
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while offset + NLMSG_HEADER <= buffer.len() {
+        let length = u32::from_ne_bytes(buffer[offset..offset + 4].try_into().unwrap()) as usize;
+        let message_type = u16::from_ne_bytes(buffer[offset + 4..offset + 6].try_into().unwrap());
+        if length < NLMSG_HEADER || offset + length > buffer.len() {
+            return Err("Truncated netlink message".to_string());
+        }
+
+        match message_type {
+            NLMSG_DONE => return Ok((entries, true)),
+            NLMSG_ERROR => return Err("Netlink neighbor dump failed".to_string()),
+            RTM_NEWNEIGH if length >= NLMSG_HEADER + NDMSG => {
+                let message = &buffer[offset + NLMSG_HEADER..offset + length];
+                let state = u16::from_ne_bytes(message[8..10].try_into().unwrap());
+
+                let mut ip = None;
+                let mut mac = None;
+                let mut attribute = NDMSG;
+                while attribute + 4 <= message.len() {
+                    let attribute_length =
+                        u16::from_ne_bytes(message[attribute..attribute + 2].try_into().unwrap())
+                            as usize;
+                    let attribute_type = u16::from_ne_bytes(
+                        message[attribute + 2..attribute + 4].try_into().unwrap(),
+                    );
+                    if attribute_length < 4 || attribute + attribute_length > message.len() {
+                        break;
+                    }
+
+                    let data = &message[attribute + 4..attribute + attribute_length];
+                    match (attribute_type, data.len()) {
+                        (NDA_DST, 4) => {
+                            ip = Some(IpAddr::V4(Ipv4Addr::from(
+                                <[u8; 4]>::try_from(data).unwrap(),
+                            )))
+                        }
+                        (NDA_DST, 16) => {
+                            ip = Some(IpAddr::V6(Ipv6Addr::from(
+                                <[u8; 16]>::try_from(data).unwrap(),
+                            )))
+                        }
+                        (NDA_LLADDR, 6) => mac = <[u8; 6]>::try_from(data).ok(),
+                        _ => {}
+                    }
+
+                    // Attributes are aligned to 4 bytes
+                    attribute += (attribute_length + 3) & !3;
+                }
+
+                if let (Some(ip), Some(mac), Some(state)) = (ip, mac, neighbor_state(state)) {
+                    entries.push(NeighborEntry {
+                        ip,
+                        mac,
+                        state: state.to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        offset += (length + 3) & !3;
+    }
+
+    Ok((entries, false))
+}
+
+#[cfg(target_os = "linux")]
+mod netlink {
+    use super::{parse_netlink, NeighborEntry};
+    use socket2::{Domain, Protocol, Socket, Type};
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    const AF_NETLINK: i32 = 16;
+    const NETLINK_ROUTE: i32 = 0;
+    const RTM_GETNEIGH: u16 = 30;
+    const NLM_F_REQUEST: u16 = 0x01;
+    const NLM_F_DUMP: u16 = 0x300;
+
+    // Asks the kernel for its neighbor table, the same one `ip neigh` shows
+    pub(super) fn dump_neighbors() -> Result<Vec<NeighborEntry>, String> {
+        let socket = Socket::new(
+            Domain::from(AF_NETLINK),
+            Type::RAW,
+            Some(Protocol::from(NETLINK_ROUTE)),
+        )
+        .map_err(|e| e.to_string())?;
+        // Only used for send and recv, which are the same for any datagram socket
+        let socket: UdpSocket = socket.into();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .map_err(|e| e.to_string())?;
+
+        // nlmsghdr followed by an ndmsg for any address family. Without a destination the
+        // message goes to the kernel.
+        let mut request = Vec::with_capacity(28);
+        request.extend(28u32.to_ne_bytes());
+        request.extend(RTM_GETNEIGH.to_ne_bytes());
+        request.extend((NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        request.extend(1u32.to_ne_bytes());
+        request.extend(0u32.to_ne_bytes());
+        request.extend([0u8; 12]);
+        socket.send(&request).map_err(|e| e.to_string())?;
+
+        let mut entries = Vec::new();
+        let mut buffer = vec![0u8; 32768];
+        loop {
+            let length = socket.recv(&mut buffer).map_err(|e| e.to_string())?;
+            let (mut received, done) = parse_netlink(&buffer[..length])?;
+            entries.append(&mut received);
+            if done {
+                return Ok(entries);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c];
+
+    #[test]
+    fn test_parse_proc_arp() {
+        let content =
+            "IP address       HW type     Flags       HW address            Mask     Device
+10.0.20.5        0x1         0x2         00:1b:21:aa:bb:0c     *        eth0
+10.0.20.6        0x1         0x0         00:00:00:00:00:00     *        eth0
+10.0.20.7        0x1         0x6         00:1b:21:aa:bb:0d     *        eth0
+";
+        let entries = parse_proc_arp(content);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            find_mac(&entries, &MAC),
+            Some(&NeighborEntry {
+                ip: "10.0.20.5".parse().unwrap(),
+                mac: MAC,
+                state: "complete".to_string(),
+            })
+        );
+        assert_eq!(entries[1].state, "permanent");
+    }
+
+    fn attribute(attribute_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut attribute = Vec::new();
+        attribute.extend((4 + data.len() as u16).to_ne_bytes());
+        attribute.extend(attribute_type.to_ne_bytes());
+        attribute.extend(data);
+        attribute.resize((attribute.len() + 3) & !3, 0);
+        attribute
+    }
+
+    fn message(message_type: u16, body: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend((16 + body.len() as u32).to_ne_bytes());
+        message.extend(message_type.to_ne_bytes());
+        message.extend([0u8; 10]);
+        message.extend(body);
+        message
+    }
+
+    fn neighbor(state: u16, ip: &[u8]) -> Vec<u8> {
+        let mut body = vec![0u8; 12];
+        body[8..10].copy_from_slice(&state.to_ne_bytes());
+        body.extend(attribute(1, ip));
+        body.extend(attribute(2, &MAC));
+        message(28, &body)
+    }
+
+    #[test]
+    fn test_parse_netlink() {
+        let mut ipv6 = [0u8; 16];
+        ipv6[..2].copy_from_slice(&[0xfe, 0x80]);
+        ipv6[15] = 1;
+
+        let mut buffer = neighbor(0x04, &[10, 0, 20, 5]);
+        buffer.extend(neighbor(0x02, &ipv6));
+        buffer.extend(neighbor(0x01, &[10, 0, 20, 6]));
+        let (entries, done) = parse_netlink(&buffer).unwrap();
+
+        assert!(!done);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].ip, "10.0.20.5".parse::<IpAddr>().unwrap());
+        assert_eq!(entries[0].state, "stale");
+        assert_eq!(entries[1].ip, "fe80::1".parse::<IpAddr>().unwrap());
+
+        let (entries, done) = parse_netlink(&message(3, &[0; 4])).unwrap();
+        assert!(done);
+        assert!(entries.is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::mac::MacAddress;
+use crate::snmp::SnmpValue;
+use crate::switch::SwitchPort;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// BRIDGE-MIB dot1dTpFdbPort, indexed by MAC address
+pub const DOT1D_TP_FDB_PORT: [u64; 11] = [1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 2];
+// Q-BRIDGE-MIB dot1qTpFdbPort, indexed by dot1qFdbId and MAC address
+pub const DOT1Q_TP_FDB_PORT: [u64; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 2, 2, 1, 2];
+// dot1dBasePortIfIndex, the ifIndex of each bridge port
+pub const DOT1D_BASE_PORT_IF_INDEX: [u64; 11] = [1, 3, 6, 1, 2, 1, 17, 1, 4, 1, 2];
+// dot1qVlanFdbId, the filtering database of each VLAN, indexed by time mark and VLAN
+pub const DOT1Q_VLAN_FDB_ID: [u64; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 2, 1, 3];
+
+// A MAC address learned on a bridge port
+#[derive(Clone, Debug, PartialEq)]
+pub struct FdbEntry {
+    pub fdb_id: Option<u64>,
+    pub mac: MacAddress,
+    pub bridge_port: u64,
+}
+
+// The forwarding database of a switch, from Q-BRIDGE-MIB when the switch has VLANs and
+// BRIDGE-MIB otherwise
+#[derive(Clone, Debug, Default)]
+pub struct ForwardingTable {
+    entries: Vec<FdbEntry>,
+}
+
+impl ForwardingTable {
+    pub fn from_dot1q(entries: Vec<(Vec<u64>, SnmpValue)>) -> Self {
+        Self::parse(entries, true)
+    }
+
+    pub fn from_dot1d(entries: Vec<(Vec<u64>, SnmpValue)>) -> Self {
+        Self::parse(entries, false)
+    }
+
+    // Port 0 holds the switch's own addresses and ones it has not learned a port for
+    fn parse(entries: Vec<(Vec<u64>, SnmpValue)>, fdb_index: bool) -> Self {
+        let entries = entries
+            .into_iter()
+            .filter_map(|(index, value)| {
+                let (fdb_id, mac) = match (fdb_index, index.as_slice()) {
+                    (true, [fdb_id, mac @ ..]) => (Some(*fdb_id), mac),
+                    (false, mac) => (None, mac),
+                    _ => return None,
+                };
+
+                let mac: Vec<u8> = mac
+                    .iter()
+                    .map(|octet| u8::try_from(*octet).ok())
+                    .collect::<Option<Vec<u8>>>()?;
+                let mac = <[u8; 6]>::try_from(mac).ok()?;
+
+                match value {
+                    SnmpValue::Integer(port) if port > 0 => Some(FdbEntry {
+                        fdb_id,
+                        mac: MacAddress::from(mac),
+                        bridge_port: port as u64,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn has_fdb_ids(&self) -> bool {
+        self.entries.iter().any(|entry| entry.fdb_id.is_some())
+    }
+
+    pub fn find(&self, mac: &MacAddress) -> Vec<&FdbEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.mac == *mac)
+            .collect()
+    }
+
+    // Addresses learned on a bridge port, many of them means the port leads to another
+    // switch rather than to the device itself
+    pub fn macs_on(&self, bridge_port: u64) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.bridge_port == bridge_port)
+            .count()
+    }
+}
+
+// The ifIndex of each bridge port
+pub fn parse_bridge_ports(entries: Vec<(Vec<u64>, SnmpValue)>) -> BTreeMap<u64, u64> {
+    entries
+        .into_iter()
+        .filter_map(|(index, value)| match (index.as_slice(), value) {
+            ([bridge_port], SnmpValue::Integer(if_index)) if if_index > 0 => {
+                Some((*bridge_port, if_index as u64))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// The VLAN of each filtering database. Switches with one database per VLAN often use
+// the VLAN ID as the database ID and do not fill this in.
+pub fn parse_vlan_fdb_ids(entries: Vec<(Vec<u64>, SnmpValue)>) -> BTreeMap<u64, u64> {
+    entries
+        .into_iter()
+        .filter_map(|(index, value)| match (index.as_slice(), value) {
+            ([_, vlan], SnmpValue::Unsigned(fdb_id)) => Some((fdb_id, *vlan)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Where a switch has learned a MAC address
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MacLocation {
+    pub switch: String,
+    pub group: Option<u64>,
+    pub port: Option<u64>,
+    pub if_index: u64,
+    pub vlan: Option<u64>,
+    pub macs_on_port: usize,
+}
+
+impl MacLocation {
+    pub fn get_port(&self) -> Option<SwitchPort> {
+        self.port.map(|port| SwitchPort {
+            group: self.group,
+            port,
+        })
+    }
+}
+
+impl std::fmt::Display for MacLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.get_port() {
+            Some(port) => write!(f, "{} port {}", self.switch, port)?,
+            None => write!(f, "{} ifIndex {}", self.switch, self.if_index)?,
+        }
+
+        if let Some(vlan) = self.vlan {
+            write!(f, ", VLAN {}", vlan)?;
+        }
+
+        if self.macs_on_port > 1 {
+            write!(
+                f,
+                " ({} MACs on this port, likely an uplink)",
+                self.macs_on_port
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c];
+
+    fn index(prefix: &[u64], mac: &[u8; 6]) -> Vec<u64> {
+        prefix
+            .iter()
+            .copied()
+            .chain(mac.iter().map(|octet| *octet as u64))
+            .collect()
+    }
+
+    #[test]
+    fn test_forwarding_table_dot1q() {
+        let other = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0d];
+        let table = ForwardingTable::from_dot1q(vec![
+            (index(&[10], &MAC), SnmpValue::Integer(14)),
+            (index(&[10], &other), SnmpValue::Integer(49)),
+            (index(&[20], &other), SnmpValue::Integer(49)),
+            (index(&[1], &[0; 6]), SnmpValue::Integer(0)),
+        ]);
+
+        assert_eq!(
+            table.find(&MAC.into()),
+            vec![&FdbEntry {
+                fdb_id: Some(10),
+                mac: MAC.into(),
+                bridge_port: 14,
+            }]
+        );
+        assert_eq!(table.macs_on(49), 2);
+        assert!(table.find(&[0; 6].into()).is_empty());
+    }
+
+    #[test]
+    fn test_forwarding_table_dot1d() {
+        let table = ForwardingTable::from_dot1d(vec![(index(&[], &MAC), SnmpValue::Integer(3))]);
+
+        assert!(!table.has_fdb_ids());
+        assert_eq!(table.find(&MAC.into())[0].bridge_port, 3);
+    }
+
+    #[test]
+    fn test_mac_location_display() {
+        let location = MacLocation {
+            switch: "core".to_string(),
+            group: None,
+            port: Some(14),
+            if_index: 10114,
+            vlan: Some(10),
+            macs_on_port: 1,
+        };
+        assert_eq!(location.to_string(), "core port 14, VLAN 10");
+
+        let location = MacLocation {
+            port: None,
+            vlan: None,
+            macs_on_port: 12,
+            ..location
+        };
+        assert_eq!(
+            location.to_string(),
+            "core ifIndex 10114 (12 MACs on this port, likely an uplink)"
+        );
+    }
+}
@@ -1,28 +1,69 @@
+use crate::arp;
+use crate::bridge::MacLocation;
 use crate::device::Device;
-use crate::switch::Switch;
-use crate::wol::Wol;
+use crate::group::{Group, GroupAction, GroupRecord};
+use crate::mac::MacAddress;
+use crate::node::{Node, NodeAction, NodeRecord};
+use crate::output::{self, OutputFormat};
+use crate::rackcliargs::{NodeArgs, SwitchArgs};
+use crate::snmp::SnmpSettings;
+use crate::switch::{Switch, SwitchRecord, SwitchSecrets};
+use crate::switch_oid::{self, BrandProfile, SwitchOidBuilder};
+use crate::utils::is_interactive;
+use crate::wol::{Wol, WolRecord, WolSettings, WolStatus};
 
 use directories::ProjectDirs;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Write;
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::PathBuf;
+use std::time::Duration;
+use tokio::task::JoinSet;
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default, skip_serializing_if = "SnmpSettings::is_empty")]
+    pub snmp: SnmpSettings,
+    #[serde(default, skip_serializing_if = "WolSettings::is_empty")]
+    pub wol: WolSettings,
     pub switches: Vec<Switch>,
     pub wols: Vec<Wol>,
+    #[serde(default)]
+    pub groups: Vec<Group>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<Node>,
+    // Kept as written so that a profile that cannot be read is still saved back
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<toml::Value>,
+}
+
+#[derive(Serialize)]
+struct ConfigRecord {
+    switches: Vec<SwitchRecord>,
+    wols: Vec<WolRecord>,
+    groups: Vec<GroupRecord>,
+    nodes: Vec<NodeRecord>,
 }
 
 pub fn read_config() -> Config {
     match Config::get_config_path() {
         Ok(config_path) => match fs::read_to_string(config_path) {
             Ok(toml_content) => {
-                let mut data: Config = toml::from_str(&toml_content).unwrap();
+                let mut data = parse_config(&toml_content);
+                data.register_profiles();
                 data.switches.iter_mut().for_each(|switch| {
                     switch.get_keys();
+                    switch.set_snmp_defaults(data.snmp);
+                });
+                data.wols.iter_mut().for_each(|wol| {
+                    if let Err(e) = wol.get_mac() {
+                        eprintln!("Warning: Wol device {}: {}", wol.name, e);
+                    }
+                    wol.get_keys();
+                    wol.set_network_defaults(data.wol.clone());
                 });
                 data
             }
@@ -42,12 +83,141 @@ pub fn read_config() -> Config {
     }
 }
 
+// Settings are checked section by section before the rest of the file is read, so a
+// mistake in one is reported and the section ignored instead of failing every command
+fn parse_config(toml_content: &str) -> Config {
+    let mut table: toml::Table = toml::from_str(toml_content).unwrap();
+
+    if let Some(e) = take_invalid(&mut table, "snmp", SnmpSettings::validate) {
+        eprintln!("Ignoring [snmp] settings in config file: {}", e);
+    }
+
+    if let Some(e) = take_invalid(&mut table, "wol", WolSettings::validate) {
+        eprintln!("Ignoring [wol] settings in config file: {}", e);
+    }
+
+    for switch in entries_mut(&mut table, "switches") {
+        if let Some(e) = take_invalid(switch, "snmp", SnmpSettings::validate) {
+            eprintln!(
+                "Ignoring SNMP settings of switch {} in config file: {}",
+                entry_name(switch),
+                e
+            );
+        }
+    }
+
+    for wol in entries_mut(&mut table, "wols") {
+        if let Some(e) = take_invalid(wol, "network", WolSettings::validate) {
+            eprintln!(
+                "Ignoring network settings of Wol device {} in config file: {}",
+                entry_name(wol),
+                e
+            );
+        }
+    }
+
+    // Read from text again, for the same reason as read_section
+    toml::from_str(&table.to_string()).unwrap()
+}
+
+// The tables of an array of tables, ex: each [[switches]]
+fn entries_mut<'a>(
+    table: &'a mut toml::Table,
+    key: &str,
+) -> impl Iterator<Item = &'a mut toml::Table> {
+    table
+        .get_mut(key)
+        .and_then(toml::Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(toml::Value::as_table_mut)
+}
+
+fn entry_name(entry: &toml::Table) -> &str {
+    entry
+        .get("name")
+        .and_then(toml::Value::as_str)
+        .unwrap_or("")
+}
+
+// Removes the section when it cannot be read or is not valid, returning why
+fn take_invalid<T: DeserializeOwned>(
+    table: &mut toml::Table,
+    key: &str,
+    validate: fn(&T) -> Result<(), String>,
+) -> Option<String> {
+    let error = match read_section::<T>(table.get(key)?) {
+        Ok(section) => validate(&section).err()?,
+        Err(e) => e,
+    };
+
+    table.remove(key);
+    Some(error)
+}
+
+// Goes through text, as only the document parser accepts numbers as table keys
+fn read_section<T: DeserializeOwned>(value: &toml::Value) -> Result<T, String> {
+    let text = toml::to_string(value).map_err(|e| e.to_string())?;
+
+    toml::from_str(&text).map_err(|e| e.message().to_string())
+}
+
 impl Config {
     pub fn new() -> Self {
         Self {
+            snmp: SnmpSettings::default(),
+            wol: WolSettings::default(),
             switches: vec![],
             wols: vec![],
+            groups: vec![],
+            nodes: vec![],
+            profiles: vec![],
+        }
+    }
+
+    fn register_profiles(&self) {
+        let profiles = self.valid_profiles();
+
+        let sob = SwitchOidBuilder::with_profiles(&profiles);
+        for switch in &self.switches {
+            if sob.get_switch_oid(switch.get_brand()).is_none() {
+                eprintln!(
+                    "Switch {} uses unknown brand {}, add a brand profile for it or change its brand",
+                    switch.name,
+                    switch.get_brand()
+                );
+            }
         }
+
+        switch_oid::register_profiles(profiles);
+    }
+
+    // Invalid profiles are left in the config file so they can be fixed, but are not
+    // offered as brands
+    fn valid_profiles(&self) -> Vec<BrandProfile> {
+        let mut brand_names = SwitchOidBuilder::with_profiles(&[]).get_oid_names();
+        let mut profiles = Vec::new();
+
+        for value in &self.profiles {
+            match read_section::<BrandProfile>(value)
+                .and_then(|profile| profile.validate(&brand_names).map(|()| profile))
+            {
+                Ok(profile) => {
+                    brand_names.push(profile.name.clone());
+                    profiles.push(profile);
+                }
+                Err(e) => eprintln!(
+                    "Ignoring brand profile {}: {}",
+                    value
+                        .get("name")
+                        .and_then(toml::Value::as_str)
+                        .unwrap_or(""),
+                    e
+                ),
+            }
+        }
+
+        profiles
     }
 
     fn get_config_path() -> Result<PathBuf, String> {
@@ -57,9 +227,33 @@ impl Config {
         Ok(base_dirs.config_dir().join("config.toml"))
     }
 
-    pub fn print_config(&self) {
-        self.print_switches();
-        self.print_wols();
+    pub fn print_config(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Table => {
+                self.print_switches(format);
+                self.print_wols(format);
+                self.print_groups(format);
+                self.print_nodes(format);
+            }
+            OutputFormat::Csv => {
+                self.print_switches(format);
+                println!();
+                self.print_wols(format);
+                println!();
+                self.print_groups(format);
+                println!();
+                self.print_nodes(format);
+            }
+            _ => output::print_value(
+                format,
+                &ConfigRecord {
+                    switches: self.switches.iter().map(Switch::to_record).collect(),
+                    wols: self.wols.iter().map(Wol::to_record).collect(),
+                    groups: self.groups.iter().map(Group::to_record).collect(),
+                    nodes: self.nodes.iter().map(Node::to_record).collect(),
+                },
+            ),
+        }
     }
 
     pub fn write_config(&self) {
@@ -115,33 +309,129 @@ impl Config {
             .interact()
             .unwrap();
 
-        match dialoguer::Confirm::new()
+        let users = self.switch_users(&switch_names[switch_name]);
+        if !users.is_empty() {
+            println!(
+                "Cannot delete {}, it is used by {}",
+                switch_names[switch_name],
+                users.join(", ")
+            );
+            return;
+        }
+
+        if let Ok(true) = dialoguer::Confirm::new()
             .with_prompt(format!(
                 "Are you sure you want to delete {}?",
                 switch_names[switch_name].clone()
             ))
             .interact()
         {
-            Ok(_) => {
-                let removed_switch = self.switches.remove(switch_name);
-                removed_switch.remove_keys();
+            let removed_switch = self.switches.remove(switch_name);
+            removed_switch.remove_keys();
+        }
+    }
+
+    pub async fn disable_switch(
+        &mut self,
+        name: Option<String>,
+        ports: Option<String>,
+        format: OutputFormat,
+    ) {
+        if let Some(switch_index) =
+            self.select_switch("Switch to disable".to_string(), name, format)
+        {
+            self.switches[switch_index].select_ports(ports);
+            self.switches[switch_index].select_output(format);
+            if let Err(e) = self.switches[switch_index].disable().await {
+                output::print_notice(format, e);
+            }
+        }
+    }
+
+    pub async fn enable_switch(
+        &mut self,
+        name: Option<String>,
+        ports: Option<String>,
+        format: OutputFormat,
+    ) {
+        if let Some(switch_index) = self.select_switch("Switch to enable".to_string(), name, format)
+        {
+            self.switches[switch_index].select_ports(ports);
+            self.switches[switch_index].select_output(format);
+            if let Err(e) = self.switches[switch_index].enable().await {
+                output::print_notice(format, e);
             }
-            Err(_) => return,
         }
     }
 
-    pub async fn disable_switch(&mut self) {
-        if let Some(switch_index) = self.select_switch("Switch to disable".to_string()) {
-            let _ = self.switches[switch_index].disable().await;
+    pub async fn cycle_switch(
+        &mut self,
+        name: Option<String>,
+        ports: Option<String>,
+        delay: Duration,
+        format: OutputFormat,
+    ) {
+        if let Some(switch_index) =
+            self.select_switch("Switch to power cycle".to_string(), name, format)
+        {
+            self.switches[switch_index].select_ports(ports);
+            self.switches[switch_index].select_output(format);
+            if let Err(e) = self.switches[switch_index].cycle(delay).await {
+                output::print_notice(format, e);
+            }
         }
     }
 
-    pub async fn enable_switch(&mut self) {
-        if let Some(switch_index) = self.select_switch("Switch to enable".to_string()) {
-            let _ = self.switches[switch_index].enable().await;
+    pub async fn identify_switch(&mut self, name: Option<String>, format: OutputFormat) {
+        if let Some(switch_index) =
+            self.select_switch("Switch to identify".to_string(), name, format)
+        {
+            match self.switches[switch_index].identify().await {
+                Ok(identity) if format == OutputFormat::Table => print!("{}", identity),
+                Ok(identity) => output::print_records(format, &[identity]),
+                Err(e) => output::print_notice(format, e),
+            }
         }
     }
 
+    // Returns whether the discovered ports were stored and the config needs writing
+    pub async fn discover_switch(&mut self, name: Option<String>) -> bool {
+        if let Some(switch_index) =
+            self.select_switch("Switch to discover".to_string(), name, OutputFormat::Table)
+        {
+            let switch = &mut self.switches[switch_index];
+            match switch.discover().await {
+                Ok(()) => {
+                    println!("PoE ports on {}: {}", switch.name, switch.get_poe_ports());
+                    match switch.map_interfaces().await {
+                        Ok(count) => println!("Interfaces on {}: {} matched", switch.name, count),
+                        Err(e) => eprintln!("Could not map interfaces: {}", e),
+                    }
+                    return true;
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+
+        false
+    }
+
+    // Groups and nodes with ports on the switch, which must be changed or deleted before it is
+    fn switch_users(&self, name: &str) -> Vec<String> {
+        let groups = self
+            .groups
+            .iter()
+            .filter(|group| group.uses_switch(name))
+            .map(|group| format!("group {}", group.name));
+        let nodes = self
+            .nodes
+            .iter()
+            .filter(|node| node.uses_switch(name))
+            .map(|node| format!("node {}", node.name));
+
+        groups.chain(nodes).collect()
+    }
+
     pub fn get_switch_names(&self) -> Vec<String> {
         let switch_names: Vec<String> = self
             .switches
@@ -154,20 +444,82 @@ impl Config {
         switch_names
     }
 
-    pub async fn get_switch_status(&mut self) {
-        if let Some(switch_index) = self.select_switch("Switch to get status".to_string()) {
-            self.switches[switch_index].status().await;
+    pub async fn get_switch_status(
+        &mut self,
+        name: Option<String>,
+        ports: Option<String>,
+        interfaces: bool,
+        format: OutputFormat,
+    ) -> bool {
+        if let Some(switch_index) =
+            self.select_switch("Switch to get status".to_string(), name, format)
+        {
+            let switch = &mut self.switches[switch_index];
+            let mapped = switch.has_interfaces();
+            switch.select_ports(ports);
+            switch.select_interfaces(interfaces);
+            switch.select_output(format);
+            switch.status().await;
+
+            // Interfaces mapped for the first time are kept for the next status
+            return !mapped && switch.has_interfaces();
+        }
+
+        false
+    }
+
+    pub async fn get_switch_neighbors(
+        &mut self,
+        name: Option<String>,
+        ports: Option<String>,
+        format: OutputFormat,
+    ) -> bool {
+        if let Some(switch_index) =
+            self.select_switch("Switch to show neighbors".to_string(), name, format)
+        {
+            let switch = &mut self.switches[switch_index];
+            let mapped = switch.has_interfaces();
+            switch.select_ports(ports);
+            switch.select_neighbors(true);
+            switch.select_output(format);
+            switch.status().await;
+
+            return !mapped && switch.has_interfaces();
         }
+
+        false
     }
 
-    pub fn update_switch(&mut self) {
-        if let Some(switch_index) = self.select_switch("Switch to update".to_string()) {
+    pub fn update_switch(&mut self, secrets: SwitchSecrets) {
+        if let Some(switch_index) =
+            self.select_switch("Switch to update".to_string(), None, OutputFormat::Table)
+        {
+            self.switches[switch_index].select_secrets(secrets);
             self.switches[switch_index].update();
             self.switches[switch_index].set_keys();
         }
     }
 
-    pub fn print_switches(&self) {
+    pub fn update_switch_from_args(&mut self, args: SwitchArgs) -> Result<(), String> {
+        let name = args.name.clone().ok_or("Name is required")?;
+        let switch = self
+            .switches
+            .iter_mut()
+            .find(|switch| switch.name == name)
+            .ok_or_else(|| format!("Switch {} not found", name))?;
+
+        switch.apply_args(args)?;
+        switch.set_keys();
+        Ok(())
+    }
+
+    pub fn print_switches(&self, format: OutputFormat) {
+        if format != OutputFormat::Table {
+            let records: Vec<SwitchRecord> = self.switches.iter().map(Switch::to_record).collect();
+            output::print_records(format, &records);
+            return;
+        }
+
         println!("Switches:");
 
         if self.switches.is_empty() {
@@ -179,9 +531,30 @@ impl Config {
         }
     }
 
-    fn select_switch(&self, prompt: String) -> Option<usize> {
+    fn select_switch(
+        &self,
+        prompt: String,
+        name: Option<String>,
+        format: OutputFormat,
+    ) -> Option<usize> {
         if self.switches.is_empty() {
-            println!("No Switches configured");
+            output::print_notice(format, "No Switches configured");
+            return None;
+        }
+
+        if let Some(name) = name {
+            let switch_index = self.switches.iter().position(|switch| switch.name == name);
+            if switch_index.is_none() {
+                output::print_notice(format, format!("Switch {} not found", name));
+            }
+            return switch_index;
+        }
+
+        if !is_interactive() {
+            output::print_notice(
+                format,
+                "No switch given, use --switch when not running interactively",
+            );
             return None;
         }
 
@@ -201,6 +574,7 @@ impl Config {
     // Wol functions
     //
     pub fn add_wol(&mut self, wol: Wol) {
+        wol.set_keys();
         self.wols.push(wol);
     }
 
@@ -219,26 +593,80 @@ impl Config {
             .interact()
             .unwrap();
 
-        match dialoguer::Confirm::new()
+        let users = self.wol_users(&wol_names[wol_name]);
+        if !users.is_empty() {
+            println!(
+                "Cannot delete {}, it is used by {}",
+                wol_names[wol_name],
+                users.join(", ")
+            );
+            return;
+        }
+
+        if let Ok(true) = dialoguer::Confirm::new()
             .with_prompt(format!(
                 "Are you sure you want to delete {}?",
                 wol_names[wol_name]
             ))
             .interact()
         {
-            Ok(_) => {
-                self.wols.remove(wol_name);
+            if let Some(wol_index) = self
+                .wols
+                .iter()
+                .position(|wol| wol.name == wol_names[wol_name])
+            {
+                let removed_wol = self.wols.remove(wol_index);
+                removed_wol.remove_keys();
+            }
+        }
+    }
+
+    // Returns false when the packet could not be sent or the device did not wake up in time
+    pub async fn enable_wol(
+        &mut self,
+        name: Option<String>,
+        wait: Duration,
+        resend_interval: Duration,
+    ) -> bool {
+        let Some(wol_index) = self.select_wol("Wol device to enable".to_string(), name) else {
+            return false;
+        };
+
+        let wol = &mut self.wols[wol_index];
+        wol.select_wait(wait, resend_interval);
+        match wol.enable().await {
+            Ok(()) => true,
+            Err(e) => {
+                println!("{}", e);
+                false
             }
-            Err(_) => return,
         }
     }
 
-    pub async fn enable_wol(&mut self) {
-        if let Some(wol_index) = self.select_wol("Wol device to enable".to_string()) {
-            let _ = self.wols[wol_index].enable().await;
+    // Returns false when the device could not be shut down
+    pub async fn disable_wol(&mut self, name: Option<String>) -> bool {
+        let Some(wol_index) = self.select_wol("Wol device to disable".to_string(), name) else {
+            return false;
+        };
+
+        match self.wols[wol_index].disable().await {
+            Ok(()) => true,
+            Err(e) => {
+                println!("{}", e);
+                false
+            }
         }
     }
 
+    // Nodes woken with the device, which must be changed or deleted before it is
+    fn wol_users(&self, name: &str) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|node| node.uses_wol(name))
+            .map(|node| format!("node {}", node.name))
+            .collect()
+    }
+
     pub fn get_wol_names(&self) -> Vec<String> {
         let mut wol_names: Vec<String> = self.wols.iter().map(|wol| wol.name.clone()).collect();
         wol_names.sort();
@@ -246,13 +674,73 @@ impl Config {
         wol_names
     }
 
-    pub fn update_wol(&mut self) {
-        if let Some(wol_index) = self.select_wol("Wol device to update".to_string()) {
+    pub fn update_wol(&mut self, settings: WolSettings, reset: bool) {
+        if let Some(wol_index) = self.select_wol("Wol device to update".to_string(), None) {
             self.wols[wol_index].update();
+            self.wols[wol_index].apply_settings(settings, reset);
+            self.wols[wol_index].set_keys();
+        }
+    }
+
+    // Probes the hosts of one or all devices concurrently, then looks their MAC addresses
+    // up in the neighbor table the probes have filled in
+    pub async fn wol_status(&self, name: Option<String>, format: OutputFormat) {
+        let wols: Vec<Wol> = match name {
+            Some(name) => match self.wols.iter().find(|wol| wol.name == name) {
+                Some(wol) => vec![wol.clone()],
+                None => {
+                    output::print_notice(format, format!("Wol device {} not found", name));
+                    return;
+                }
+            },
+            None => self.wols.clone(),
+        };
+
+        if wols.is_empty() {
+            output::print_notice(format, "No Wake-on-Lan devices configured");
+            return;
+        }
+
+        let mut req_set = JoinSet::new();
+        for (index, wol) in wols.iter().cloned().enumerate() {
+            req_set.spawn(async move { (index, wol.probe_host().await) });
+        }
+
+        let mut probes = vec![None; wols.len()];
+        while let Some(result) = req_set.join_next().await {
+            match result {
+                Ok((index, probe)) => probes[index] = probe,
+                Err(e) => eprintln!("Task error: {}", e),
+            }
+        }
+
+        let neighbors = arp::neighbor_table().unwrap_or_else(|e| {
+            output::print_notice(format, e);
+            vec![]
+        });
+        let statuses: Vec<WolStatus> = wols
+            .iter()
+            .zip(probes)
+            .map(|(wol, probe)| wol.to_status(probe, &neighbors))
+            .collect();
+
+        if format != OutputFormat::Table {
+            output::print_records(format, &statuses);
+            return;
+        }
+
+        for status in statuses {
+            println!("{}", status);
         }
     }
 
-    pub fn print_wols(&self) {
+    pub fn print_wols(&self, format: OutputFormat) {
+        if format != OutputFormat::Table {
+            let records: Vec<WolRecord> = self.wols.iter().map(Wol::to_record).collect();
+            output::print_records(format, &records);
+            return;
+        }
+
         println!("Wols:");
 
         if self.wols.is_empty() {
@@ -264,12 +752,25 @@ impl Config {
         }
     }
 
-    fn select_wol(&self, prompt: String) -> Option<usize> {
+    fn select_wol(&self, prompt: String, name: Option<String>) -> Option<usize> {
         if self.wols.is_empty() {
             println!("No Wake-on-Lan devices configured");
             return None;
         }
 
+        if let Some(name) = name {
+            let wol_index = self.wols.iter().position(|wol| wol.name == name);
+            if wol_index.is_none() {
+                println!("Wol device {} not found", name);
+            }
+            return wol_index;
+        }
+
+        if !is_interactive() {
+            println!("No Wol device given, name one when not running interactively");
+            return None;
+        }
+
         let wol_names = self.get_wol_names();
 
         let wol_index = dialoguer::Select::new()
@@ -279,6 +780,418 @@ impl Config {
             .interact()
             .unwrap();
 
-        Some(wol_index)
+        // The names are sorted, find the device in config order
+        self.wols
+            .iter()
+            .position(|wol| wol.name == wol_names[wol_index])
+    }
+
+    //
+    // Group functions
+    //
+    pub fn add_group(&mut self, name: String, members: Vec<String>) -> Result<(), String> {
+        let group = Group::new(name, members, &self.switches, self.get_group_names())?;
+        self.groups.push(group);
+        Ok(())
+    }
+
+    pub fn delete_group(&mut self, name: String) -> Result<(), String> {
+        let group_index = self
+            .groups
+            .iter()
+            .position(|group| group.name == name)
+            .ok_or_else(|| format!("Group {} not found", name))?;
+
+        self.groups.remove(group_index);
+        Ok(())
+    }
+
+    pub fn get_group_names(&self) -> Vec<String> {
+        self.groups.iter().map(|group| group.name.clone()).collect()
+    }
+
+    pub async fn run_group(&self, name: String, action: GroupAction, format: OutputFormat) {
+        match self.groups.iter().find(|group| group.name == name) {
+            Some(group) => group.run(&self.switches, action, format).await,
+            None => output::print_notice(format, format!("Group {} not found", name)),
+        }
+    }
+
+    pub fn print_groups(&self, format: OutputFormat) {
+        if format != OutputFormat::Table {
+            let records: Vec<GroupRecord> = self.groups.iter().map(Group::to_record).collect();
+            output::print_records(format, &records);
+            return;
+        }
+
+        println!("Groups:");
+
+        if self.groups.is_empty() {
+            println!("  No Groups configured");
+        } else {
+            for group in &self.groups {
+                println!("{}", group);
+            }
+        }
+    }
+
+    //
+    // Node functions
+    //
+    pub fn add_node(&mut self, args: NodeArgs) -> Result<(), String> {
+        let node = Node::from_args(args, &self.switches, &self.wols, self.get_node_names())?;
+        self.nodes.push(node);
+        Ok(())
+    }
+
+    pub fn delete_node(&mut self, name: String) -> Result<(), String> {
+        let node_index = self.find_node(&name)?;
+
+        self.nodes.remove(node_index);
+        Ok(())
+    }
+
+    pub fn update_node(&mut self, name: String) -> Result<(), String> {
+        let node_index = self.find_node(&name)?;
+
+        let mut node = self.nodes[node_index].clone();
+        node.update();
+        node.validate(&self.switches, &self.wols)?;
+        self.nodes[node_index] = node;
+        Ok(())
+    }
+
+    pub fn get_node_names(&self) -> Vec<String> {
+        self.nodes.iter().map(|node| node.name.clone()).collect()
+    }
+
+    fn find_node(&self, name: &str) -> Result<usize, String> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .ok_or_else(|| format!("Node {} not found", name))
+    }
+
+    pub async fn run_node(
+        &self,
+        name: String,
+        action: NodeAction,
+        timeout: Duration,
+        format: OutputFormat,
+    ) {
+        let mut node = match self.find_node(&name) {
+            Ok(node_index) => self.nodes[node_index].clone(),
+            Err(e) => {
+                output::print_notice(format, e);
+                return;
+            }
+        };
+
+        if let Err(e) = node.attach(&self.switches, &self.wols) {
+            output::print_notice(format, format!("{}: {}", node.name, e));
+            return;
+        }
+
+        node.select_timeout(timeout);
+        node.select_output(format);
+        if let Err(e) = node.run(action).await {
+            output::print_notice(format, e);
+        }
+    }
+
+    pub fn print_nodes(&self, format: OutputFormat) {
+        if format != OutputFormat::Table {
+            let records: Vec<NodeRecord> = self.nodes.iter().map(Node::to_record).collect();
+            output::print_records(format, &records);
+            return;
+        }
+
+        println!("Nodes:");
+
+        if self.nodes.is_empty() {
+            println!("  No Nodes configured");
+        } else {
+            for node in &self.nodes {
+                println!("{}", node);
+            }
+        }
+    }
+
+    //
+    // Locate functions
+    //
+
+    // Searches the forwarding table of every switch for a MAC address or the MAC of a Wol
+    // device. Returns true when the config changed and needs to be written.
+    pub async fn locate(&mut self, target: String, save: bool, format: OutputFormat) -> bool {
+        let wol_index = self.wols.iter().position(|wol| wol.name == target);
+        let mac = match wol_index {
+            Some(index) => self.wols[index].get_mac(),
+            None => match target.parse::<MacAddress>() {
+                Ok(mac) => mac.check_unicast().map(|_| mac),
+                Err(_) => Err(format!("{} is not a Wol device or a MAC address", target)),
+            },
+        };
+        let mac = match mac {
+            Ok(mac) => mac,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        };
+
+        // Credentials are loaded before searching, so prompts are not interleaved
+        let mut prepared = Vec::new();
+        for (index, switch) in self.switches.iter().enumerate() {
+            let mut switch = switch.clone();
+            match switch.load_runtime_credentials() {
+                Ok(()) => prepared.push((index, switch)),
+                Err(e) => eprintln!("Skipping {}: {}", switch.name, e),
+            }
+        }
+
+        let mut req_set = JoinSet::new();
+        for (index, mut switch) in prepared {
+            req_set.spawn(async move {
+                let mapped = switch.has_interfaces();
+                let locations = switch.locate_mac(&mac).await;
+                (index, !mapped && switch.has_interfaces(), switch, locations)
+            });
+        }
+
+        let mut changed = false;
+        let mut searched = Vec::new();
+        while let Some(result) = req_set.join_next().await {
+            match result {
+                Ok((index, newly_mapped, switch, locations)) => {
+                    if newly_mapped {
+                        self.switches[index].copy_interfaces(&switch);
+                        changed = true;
+                    }
+                    searched.push((index, switch.name, locations));
+                }
+                Err(e) => eprintln!("Task error: {}", e),
+            }
+        }
+
+        searched.sort_by_key(|(index, _, _)| *index);
+        let mut locations: Vec<MacLocation> = Vec::new();
+        for (_, name, result) in searched {
+            match result {
+                Ok(found) => locations.extend(found),
+                Err(e) => eprintln!("Could not search {}: {}", name, e),
+            }
+        }
+
+        // The port with the fewest addresses is closest to the device, the others lead
+        // to the switch it is on
+        locations.sort_by_key(|location| location.macs_on_port);
+        match format {
+            OutputFormat::Table if locations.is_empty() => {
+                println!("{} not found on any switch", mac)
+            }
+            OutputFormat::Table => {
+                println!("{} found on:", mac);
+                for location in &locations {
+                    println!("\t{}", location);
+                }
+            }
+            format => output::print_records(format, &locations),
+        }
+
+        if save {
+            changed |= self.save_location(wol_index, &mac, locations.first());
+        }
+
+        changed
+    }
+
+    fn save_location(
+        &mut self,
+        wol_index: Option<usize>,
+        mac: &MacAddress,
+        location: Option<&MacLocation>,
+    ) -> bool {
+        let wol_index =
+            wol_index.or_else(|| self.wols.iter().position(|wol| wol.get_mac() == Ok(*mac)));
+        let Some(wol_index) = wol_index else {
+            eprintln!("No Wol device has this MAC address, nothing saved");
+            return false;
+        };
+        let Some(location) = location else {
+            return false;
+        };
+
+        let wol = &mut self.wols[wol_index];
+        match wol.set_location(location) {
+            Ok(()) => {
+                eprintln!("Saved location of {}: {}", wol.name, location);
+                true
+            }
+            Err(e) => {
+                eprintln!("Could not save location of {}: {}", wol.name, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWITCH: &str = "[[switches]]
+name = \"core\"
+ip = \"10.0.0.2\"
+brand = \"Netgear\"
+version = \"V2\"
+ports = 24
+keyring = false
+auth = \"Sha256\"
+auth_user = \"\"
+encryption = \"None\"
+";
+
+    #[test]
+    fn test_parse_config_ignores_invalid_snmp_settings() {
+        let config = parse_config(&format!(
+            "wols = []\n[snmp]\ntimeout = 0\n{}[switches.snmp]\nport = 1161\n",
+            SWITCH
+        ));
+        assert!(config.snmp.is_empty());
+        assert_eq!(config.switches[0].to_record().snmp_port, Some(1161));
+
+        let config = parse_config(&format!(
+            "wols = []\n[snmp]\ntimeout = 2\n{}[switches.snmp]\nretries = 50\n",
+            SWITCH
+        ));
+        assert_eq!(config.snmp.timeout, Some(2));
+        assert_eq!(config.switches[0].to_record().retries, None);
+    }
+
+    #[test]
+    fn test_parse_config_ignores_unknown_snmp_keys() {
+        let config = parse_config(&format!(
+            "wols = []\n[snmp]\ntimout = 2\n{}[switches.snmp]\nport = \"x\"\n",
+            SWITCH
+        ));
+        assert!(config.snmp.is_empty());
+        assert_eq!(config.switches[0].to_record().snmp_port, None);
+    }
+
+    #[test]
+    fn test_parse_config_ignores_invalid_wol_settings() {
+        let config = parse_config(
+            "switches = []
+[wol]
+port = 0
+
+[[wols]]
+name = \"nas\"
+mac = \"00:1B:21:AA:BB:0C\"
+
+[wols.network]
+interface = \"\"
+
+[[wols]]
+name = \"nuc\"
+mac = \"00:1B:21:AA:BB:0D\"
+
+[wols.network]
+port = 7
+",
+        );
+        assert!(config.wol.is_empty());
+        assert!(config.wols[0].get_settings().is_empty());
+        assert_eq!(config.wols[1].get_settings().port, Some(7));
+    }
+
+    #[test]
+    fn test_parse_config_keeps_wol_with_invalid_mac() {
+        let config = parse_config(
+            "switches = []
+
+[[wols]]
+name = \"nas\"
+mac = \"00:1b\"
+
+[[wols]]
+name = \"nuc\"
+mac = \"001b.21aa.bb0d\"
+",
+        );
+        assert_eq!(config.wols.len(), 2);
+        assert!(config.wols[0].get_mac().is_err());
+        assert_eq!(
+            config.wols[1].get_mac().map(|mac| mac.to_string()),
+            Ok("00:1B:21:AA:BB:0D".to_string())
+        );
+    }
+
+    #[test]
+    fn test_switch_users() {
+        let config = parse_config(&format!(
+            "wols = []
+{}
+[[groups]]
+name = \"cameras\"
+members = [\"edge:1-4\", \"core:lobby-camera\"]
+
+[[groups]]
+name = \"lab\"
+members = [\"edge:5\"]
+
+[[nodes]]
+name = \"pi\"
+port = \"core:5\"
+wol = \"nas\"
+
+[[nodes]]
+name = \"nuc\"
+port = \"edge:6\"
+",
+            SWITCH
+        ));
+        assert_eq!(
+            config.switch_users("core"),
+            vec!["group cameras", "node pi"]
+        );
+        assert!(config.switch_users("backup").is_empty());
+        assert_eq!(config.wol_users("nas"), vec!["node pi"]);
+        assert!(config.wol_users("nuc").is_empty());
+    }
+
+    #[test]
+    fn test_valid_profiles() {
+        let config = parse_config(
+            "switches = []
+wols = []
+
+[[profiles]]
+name = \"Acme\"
+oid = \"1.3.6.1.4.1.99.1\"
+
+[profiles.status]
+3 = \"Fault\"
+
+[[profiles]]
+name = \"Typo\"
+oid = \"1.3.6.1.4.1.99.2\"
+of = 3
+
+[[profiles]]
+name = \"Broken\"
+oid = \"1.3.x\"
+",
+        );
+
+        let profiles = config.valid_profiles();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "Acme");
+        assert_eq!(profiles[0].status.get(&3), Some(&"Fault".to_string()));
+
+        // Profiles that are ignored are still written back
+        assert_eq!(config.profiles.len(), 3);
+        assert!(toml::to_string(&config).unwrap().contains("of = 3"));
     }
 }
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,4 +7,10 @@ pub enum SnmpError {
     SessionError(String),
     #[error("SNMP operation failed: {0}")]
     OperationError(String),
+    #[error("SNMP request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("SNMP response would be too big")]
+    TooBig,
+    #[error("SNMP agent returned {0}")]
+    AgentError(String),
 }
@@ -0,0 +1,295 @@
+use crate::output::{self, OutputFormat};
+use crate::switch::{Switch, SwitchResult};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+// A named set of ports spread over one or more switches
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Group {
+    pub name: String,
+    members: Vec<String>,
+}
+
+// Group settings as shown by list
+#[derive(Serialize)]
+pub struct GroupRecord {
+    pub name: String,
+    pub members: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupAction {
+    Enable,
+    Disable,
+    Status,
+    Cycle(Duration),
+}
+
+// A group member, ex: core:1-4,lobby-camera
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupMember {
+    pub switch: String,
+    pub ports: String,
+}
+
+impl FromStr for GroupMember {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (switch, ports) = input
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid group member: {}, expected SWITCH:PORTS", input))?;
+
+        if switch.trim().is_empty() || ports.trim().is_empty() {
+            return Err(format!(
+                "Invalid group member: {}, expected SWITCH:PORTS",
+                input
+            ));
+        }
+
+        Ok(Self {
+            switch: switch.trim().to_string(),
+            ports: ports.trim().to_string(),
+        })
+    }
+}
+
+impl Group {
+    pub fn new(
+        name: String,
+        members: Vec<String>,
+        switches: &[Switch],
+        group_names: Vec<String>,
+    ) -> Result<Self, String> {
+        if name.trim().is_empty() {
+            return Err("Name is required".to_string());
+        }
+
+        if group_names.contains(&name) {
+            return Err(format!("Group {} already exists", name));
+        }
+
+        if members.is_empty() {
+            return Err("A group needs at least one member".to_string());
+        }
+
+        let group = Self { name, members };
+        for member in group.get_members()? {
+            member_switch(&member, switches)?;
+        }
+
+        Ok(group)
+    }
+
+    pub(crate) fn get_members(&self) -> Result<Vec<GroupMember>, String> {
+        self.members
+            .iter()
+            .map(|member| member.parse::<GroupMember>())
+            .collect()
+    }
+
+    pub fn uses_switch(&self, name: &str) -> bool {
+        self.members
+            .iter()
+            .filter_map(|member| member.parse::<GroupMember>().ok())
+            .any(|member| member.switch == name)
+    }
+
+    pub fn to_record(&self) -> GroupRecord {
+        GroupRecord {
+            name: self.name.clone(),
+            members: self.members.join(" "),
+        }
+    }
+
+    pub async fn run(&self, switches: &[Switch], action: GroupAction, format: OutputFormat) {
+        // Every member is checked and its credentials loaded before any port is touched,
+        // so a typo does not leave the group half powered and prompts are not interleaved
+        let members = match self.get_members() {
+            Ok(members) => members,
+            Err(e) => {
+                output::print_notice(format, e);
+                return;
+            }
+        };
+
+        let mut prepared = Vec::new();
+        for member in members {
+            let mut switch = match member_switch(&member, switches) {
+                Ok(switch) => switch,
+                Err(e) => {
+                    output::print_notice(format, e);
+                    return;
+                }
+            };
+
+            switch.select_output(format);
+            match switch.prepare() {
+                Ok(ports) => prepared.push((switch, ports)),
+                Err(e) => {
+                    output::print_notice(format, format!("{}: {}", switch.name, e));
+                    return;
+                }
+            }
+        }
+
+        let mut req_set = JoinSet::new();
+        for (index, (mut switch, ports)) in prepared.into_iter().enumerate() {
+            req_set.spawn(async move {
+                let results = match action {
+                    GroupAction::Enable => switch.power_on().await,
+                    GroupAction::Disable => switch.power_off().await,
+                    GroupAction::Status => switch.port_status().await,
+                    GroupAction::Cycle(delay) => switch.power_cycle(delay).await,
+                };
+
+                // A failed member is reported as an error entry for each of its ports
+                let results = results.unwrap_or_else(|e| {
+                    ports
+                        .iter()
+                        .map(|port| SwitchResult::error(&switch.name, *port, &e))
+                        .collect()
+                });
+
+                (index, results)
+            });
+        }
+
+        let mut member_results = Vec::new();
+        while let Some(result) = req_set.join_next().await {
+            match result {
+                Ok(result) => member_results.push(result),
+                Err(e) => eprintln!("Task error: {}", e),
+            }
+        }
+
+        // Report members in the order they are configured
+        member_results.sort_by_key(|(index, _)| *index);
+        self.report(
+            member_results.into_iter().map(|(_, results)| results),
+            format,
+        );
+    }
+
+    fn report(
+        &self,
+        member_results: impl Iterator<Item = Vec<SwitchResult>>,
+        format: OutputFormat,
+    ) {
+        match format {
+            OutputFormat::Table => {
+                println!("Status for group {}:", self.name);
+                for results in member_results {
+                    if let Some(first) = results.first() {
+                        println!("  {}:", first.switch);
+                    }
+                    for result in results {
+                        println!("\t{}", result);
+                    }
+                }
+            }
+            format => {
+                let results: Vec<SwitchResult> = member_results.flatten().collect();
+                output::print_records(format, &results);
+            }
+        }
+    }
+}
+
+// Finds the member's switch and selects the member's ports on a copy of it
+pub(crate) fn member_switch(member: &GroupMember, switches: &[Switch]) -> Result<Switch, String> {
+    let mut switch = switches
+        .iter()
+        .find(|switch| switch.name == member.switch)
+        .cloned()
+        .ok_or_else(|| format!("Switch {} not found", member.switch))?;
+
+    switch.select_ports(Some(member.ports.clone()));
+    switch.get_ports()?;
+
+    Ok(switch)
+}
+
+impl std::fmt::Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "  Name: {}\n  Members: {}\n",
+            self.name,
+            self.members.join(" ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::switch::tests::switches;
+
+    #[test]
+    fn test_group_member_from_str() {
+        assert_eq!(
+            "core:1-4,8".parse::<GroupMember>(),
+            Ok(GroupMember {
+                switch: "core".to_string(),
+                ports: "1-4,8".to_string(),
+            })
+        );
+        assert!("core".parse::<GroupMember>().is_err());
+        assert!("core:".parse::<GroupMember>().is_err());
+    }
+
+    #[test]
+    fn test_new_group_valid() {
+        let group = Group::new(
+            "cameras".to_string(),
+            vec!["core:1-4".to_string(), "core:8".to_string()],
+            &switches(),
+            vec![],
+        )
+        .unwrap();
+
+        assert_eq!(group.to_record().members, "core:1-4 core:8");
+    }
+
+    #[test]
+    fn test_new_group_unknown_switch() {
+        let result = Group::new(
+            "cameras".to_string(),
+            vec!["edge:1-4".to_string()],
+            &switches(),
+            vec![],
+        );
+
+        assert_eq!(result.err(), Some("Switch edge not found".to_string()));
+    }
+
+    #[test]
+    fn test_new_group_invalid_ports() {
+        let result = Group::new(
+            "cameras".to_string(),
+            vec!["core:4-x".to_string()],
+            &switches(),
+            vec![],
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_new_group_duplicate_name() {
+        let result = Group::new(
+            "cameras".to_string(),
+            vec!["core:1".to_string()],
+            &switches(),
+            vec!["cameras".to_string()],
+        );
+
+        assert_eq!(
+            result.err(),
+            Some("Group cameras already exists".to_string())
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use crate::snmp::SnmpValue;
+use crate::switch::SwitchPort;
+use crate::utils::{get_oper_status, get_text};
+use std::collections::BTreeMap;
+
+// IF-MIB ifTable and ifXTable columns, indexed by ifIndex
+pub const IF_TYPE: [u64; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 3];
+pub const IF_OPER_STATUS: [u64; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 8];
+pub const IF_NAME: [u64; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 1];
+pub const IF_HIGH_SPEED: [u64; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 15];
+pub const IF_ALIAS: [u64; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 18];
+
+// ifType of physical ethernet ports, VLAN and loopback interfaces have other types
+const IF_TYPE_ETHERNET: i64 = 6;
+
+// What IF-MIB reports about the interface of a port
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Interface {
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    pub oper_status: Option<String>,
+    pub speed_mbps: Option<u64>,
+}
+
+impl Interface {
+    // Values in the order of the columns read by status: ifName, ifAlias, ifOperStatus
+    // and ifHighSpeed. Empty strings and missing objects are left unset.
+    pub fn from_values(values: &[SnmpValue]) -> Self {
+        Self {
+            name: get_text(&values[0]),
+            alias: get_text(&values[1]),
+            oper_status: match values[2] {
+                SnmpValue::Integer(_) => Some(get_oper_status(&values[2])),
+                _ => None,
+            },
+            speed_mbps: match values[3] {
+                SnmpValue::Unsigned(speed) => Some(speed),
+                _ => None,
+            },
+        }
+    }
+}
+
+// The numbers in an interface name, ex: [1, 0, 14] for Gi1/0/14
+fn name_numbers(name: &str) -> Vec<u64> {
+    name.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|number| number.parse::<u64>().ok())
+        .collect()
+}
+
+// Matches the ethernet interfaces to ports by name: the last number is the port and the
+// first the stack member, ex: Gi2/0/14, 2/14, g14. Some brands count members from 0
+// (ge-0/0/14, 0/14), which shows as a port on member 0; a management port such as Gi0/0
+// is port 0 and does not count. Names with a module other than 0 are uplinks and are
+// skipped.
+pub fn map_ports(
+    types: Vec<(Vec<u64>, SnmpValue)>,
+    names: Vec<(Vec<u64>, SnmpValue)>,
+    default_group: u64,
+) -> BTreeMap<u64, BTreeMap<u64, u64>> {
+    let ethernet: Vec<u64> = types
+        .into_iter()
+        .filter_map(|(index, value)| match (index.as_slice(), value) {
+            ([if_index], SnmpValue::Integer(IF_TYPE_ETHERNET)) => Some(*if_index),
+            _ => None,
+        })
+        .collect();
+
+    let named: Vec<(u64, Vec<u64>)> = names
+        .into_iter()
+        .filter_map(|(index, value)| match index.as_slice() {
+            [if_index] if ethernet.contains(if_index) => {
+                Some((*if_index, name_numbers(&value.to_string())))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let zero_based = named
+        .iter()
+        .any(|(_, numbers)| numbers.len() > 1 && numbers[0] == 0 && numbers[numbers.len() - 1] > 0);
+
+    let mut if_indexes: BTreeMap<u64, BTreeMap<u64, u64>> = BTreeMap::new();
+    for (if_index, numbers) in named {
+        let (group, port) = match numbers.as_slice() {
+            [port] => (default_group, *port),
+            [member, port] | [member, 0, port] if zero_based => (member + 1, *port),
+            [member, port] | [member, 0, port] => (*member, *port),
+            _ => continue,
+        };
+
+        // The first interface with a name wins, later ones are usually logical units
+        if_indexes
+            .entry(group)
+            .or_default()
+            .entry(port)
+            .or_insert(if_index);
+    }
+
+    if_indexes
+}
+
+// The group and port an interface is mapped to
+pub fn find_port(
+    if_indexes: &BTreeMap<u64, BTreeMap<u64, u64>>,
+    if_index: u64,
+) -> Option<(u64, u64)> {
+    if_indexes.iter().find_map(|(group, ports)| {
+        ports
+            .iter()
+            .find(|(_, index)| **index == if_index)
+            .map(|(port, _)| (*group, *port))
+    })
+}
+
+// The ifIndex of a port, ports without a member are on the default group
+pub fn find_if_index(
+    if_indexes: &BTreeMap<u64, BTreeMap<u64, u64>>,
+    port: &SwitchPort,
+    default_group: u64,
+) -> Option<u64> {
+    if_indexes
+        .get(&port.group.unwrap_or(default_group))
+        .and_then(|ports| ports.get(&port.port))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(entries: &[(u64, SnmpValue)]) -> Vec<(Vec<u64>, SnmpValue)> {
+        entries
+            .iter()
+            .map(|(if_index, value)| (vec![*if_index], value.clone()))
+            .collect()
+    }
+
+    fn name(name: &str) -> SnmpValue {
+        SnmpValue::OctetString(name.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_map_ports_stacked_names() {
+        let types = table(&[
+            (10114, SnmpValue::Integer(6)),
+            (10214, SnmpValue::Integer(6)),
+            (10301, SnmpValue::Integer(6)),
+            (1, SnmpValue::Integer(53)),
+        ]);
+        let names = table(&[
+            (10114, name("Gi1/0/14")),
+            (10214, name("Gi2/0/14")),
+            (10301, name("Te1/1/1")),
+            (1, name("Vlan1")),
+        ]);
+
+        let if_indexes = map_ports(types, names, 1);
+
+        assert_eq!(if_indexes.len(), 2);
+        assert_eq!(if_indexes[&1][&14], 10114);
+        assert_eq!(if_indexes[&2][&14], 10214);
+        assert_eq!(find_port(&if_indexes, 10214), Some((2, 14)));
+        assert_eq!(find_port(&if_indexes, 1), None);
+    }
+
+    #[test]
+    fn test_map_ports_zero_based_and_plain_names() {
+        let types = table(&[(514, SnmpValue::Integer(6)), (614, SnmpValue::Integer(6))]);
+        let names = table(&[(514, name("ge-0/0/14")), (614, name("ge-1/0/14"))]);
+        let if_indexes = map_ports(types, names, 1);
+        assert_eq!(if_indexes[&1][&14], 514);
+        assert_eq!(if_indexes[&2][&14], 614);
+
+        let types = table(&[(3, SnmpValue::Integer(6))]);
+        let names = table(&[(3, name("g3"))]);
+        assert_eq!(
+            find_if_index(&map_ports(types, names, 1), &SwitchPort::from(3), 1),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_interface_from_values() {
+        let interface = Interface::from_values(&[
+            name("Gi1/0/14"),
+            name(""),
+            SnmpValue::Integer(1),
+            SnmpValue::Unsigned(1000),
+        ]);
+
+        assert_eq!(
+            interface,
+            Interface {
+                name: Some("Gi1/0/14".to_string()),
+                alias: None,
+                oper_status: Some("Up".to_string()),
+                speed_mbps: Some(1000),
+            }
+        );
+    }
+}
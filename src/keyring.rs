@@ -8,6 +8,7 @@ pub(crate) enum KeyRingType {
     Auth,
     Community,
     Encrypt,
+    SecureOn,
 }
 
 impl Display for KeyRingType {
@@ -16,6 +17,7 @@ impl Display for KeyRingType {
             KeyRingType::Auth => write!(f, "auth"),
             KeyRingType::Community => write!(f, "community"),
             KeyRingType::Encrypt => write!(f, "encrypt"),
+            KeyRingType::SecureOn => write!(f, "secureon"),
         }
     }
 }
@@ -0,0 +1,235 @@
+use crate::mac::MacAddress;
+use crate::snmp::SnmpValue;
+use crate::utils::get_text;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// LLDP-MIB lldpRemTable columns, indexed by lldpRemTimeMark, lldpRemLocalPortNum and
+// lldpRemIndex
+pub const LLDP_REM_PORT_ID_SUBTYPE: [u64; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 6];
+pub const LLDP_REM_PORT_ID: [u64; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 7];
+pub const LLDP_REM_SYS_NAME: [u64; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 9];
+
+// LLDP-MIB lldpLocPortTable columns, indexed by lldpLocPortNum, the number lldpRemTable
+// gives as lldpRemLocalPortNum
+pub const LLDP_LOC_PORT_ID: [u64; 11] = [1, 0, 8802, 1, 1, 2, 1, 3, 7, 1, 3];
+pub const LLDP_LOC_PORT_DESC: [u64; 11] = [1, 0, 8802, 1, 1, 2, 1, 3, 7, 1, 4];
+
+// lldpRemManAddrIfSubtype, the address itself is only in the index after the lldpRemTable
+// index: lldpRemManAddrSubtype, then the address as a length prefixed string
+pub const LLDP_REM_MAN_ADDR_IF_SUBTYPE: [u64; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 2, 1, 3];
+
+// lldpRemPortIdSubtype of a port ID that is a MAC address
+const PORT_ID_MAC_ADDRESS: i64 = 3;
+
+// IANA address families used by lldpRemManAddrSubtype
+const ADDRESS_IPV4: u64 = 1;
+const ADDRESS_IPV6: u64 = 2;
+
+// A device the switch has learned about through LLDP on one of its ports
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Neighbor {
+    pub name: Option<String>,
+    pub port_id: Option<String>,
+    pub addresses: Vec<String>,
+}
+
+// Joins the walked lldpRemTable columns into the neighbors seen on each local port,
+// keyed by lldpRemLocalPortNum
+pub fn parse_neighbors(
+    names: Vec<(Vec<u64>, SnmpValue)>,
+    port_id_subtypes: Vec<(Vec<u64>, SnmpValue)>,
+    port_ids: Vec<(Vec<u64>, SnmpValue)>,
+    addresses: Vec<(Vec<u64>, SnmpValue)>,
+) -> BTreeMap<u64, Vec<Neighbor>> {
+    let mut remotes: BTreeMap<[u64; 3], Neighbor> = BTreeMap::new();
+
+    for (index, value) in names {
+        if let Some(key) = remote_key(&index) {
+            remotes.entry(key).or_default().name = get_text(&value);
+        }
+    }
+
+    let subtypes: BTreeMap<[u64; 3], SnmpValue> = port_id_subtypes
+        .into_iter()
+        .filter_map(|(index, value)| remote_key(&index).map(|key| (key, value)))
+        .collect();
+
+    for (index, value) in port_ids {
+        if let Some(key) = remote_key(&index) {
+            let port_id = match (subtypes.get(&key), &value) {
+                (Some(SnmpValue::Integer(PORT_ID_MAC_ADDRESS)), SnmpValue::OctetString(bytes))
+                    if bytes.len() == 6 =>
+                {
+                    <[u8; 6]>::try_from(bytes.as_slice())
+                        .ok()
+                        .map(|octets| MacAddress::from(octets).to_string())
+                }
+                _ => get_text(&value),
+            };
+            remotes.entry(key).or_default().port_id = port_id;
+        }
+    }
+
+    for (index, _) in addresses {
+        if let (Some(key), Some(address)) = (remote_key(&index), parse_address(&index[3..])) {
+            remotes.entry(key).or_default().addresses.push(address);
+        }
+    }
+
+    let mut neighbors: BTreeMap<u64, Vec<Neighbor>> = BTreeMap::new();
+    for ([_, local_port, _], neighbor) in remotes {
+        neighbors.entry(local_port).or_default().push(neighbor);
+    }
+
+    neighbors
+}
+
+// Matches each lldpLocPortNum to an ifIndex, through the ifName its lldpLocPortId or
+// lldpLocPortDesc names. Local ports that name no interface are left out.
+pub fn map_local_ports(
+    port_ids: Vec<(Vec<u64>, SnmpValue)>,
+    port_descs: Vec<(Vec<u64>, SnmpValue)>,
+    if_names: Vec<(Vec<u64>, SnmpValue)>,
+) -> BTreeMap<u64, u64> {
+    let if_names: Vec<(u64, String)> = if_names
+        .into_iter()
+        .filter_map(|(index, value)| match index.as_slice() {
+            [if_index] => get_text(&value).map(|name| (*if_index, name)),
+            _ => None,
+        })
+        .collect();
+
+    let mut local_ports = BTreeMap::new();
+    for (index, value) in port_ids.into_iter().chain(port_descs) {
+        let ([local_port], Some(text)) = (index.as_slice(), get_text(&value)) else {
+            continue;
+        };
+        if local_ports.contains_key(local_port) {
+            continue;
+        }
+        if let Some((if_index, _)) = if_names
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(&text))
+        {
+            local_ports.insert(*local_port, *if_index);
+        }
+    }
+
+    local_ports
+}
+
+// lldpRemTimeMark, lldpRemLocalPortNum and lldpRemIndex
+fn remote_key(index: &[u64]) -> Option<[u64; 3]> {
+    match index {
+        [time_mark, local_port, remote, ..] => Some([*time_mark, *local_port, *remote]),
+        _ => None,
+    }
+}
+
+// lldpRemManAddrSubtype followed by lldpRemManAddr. The address length comes first
+// unless the agent encodes it as an implied length string.
+fn parse_address(index: &[u64]) -> Option<String> {
+    let (subtype, rest) = index.split_first()?;
+    let bytes: Vec<u8> = match rest.split_first() {
+        Some((length, address)) if *length as usize == address.len() => address,
+        _ => rest,
+    }
+    .iter()
+    .map(|byte| u8::try_from(*byte).ok())
+    .collect::<Option<Vec<u8>>>()?;
+
+    match (*subtype, bytes.len()) {
+        (ADDRESS_IPV4, 4) => {
+            Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string())
+        }
+        (ADDRESS_IPV6, 16) => {
+            let octets: [u8; 16] = bytes.try_into().ok()?;
+            Some(Ipv6Addr::from(octets).to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_value(text: &str) -> SnmpValue {
+        SnmpValue::OctetString(text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_parse_neighbors() {
+        let names = vec![
+            (vec![0, 3, 1], text_value("ap-lobby")),
+            (vec![0, 5, 2], text_value("")),
+        ];
+        let subtypes = vec![
+            (vec![0, 3, 1], SnmpValue::Integer(5)),
+            (vec![0, 5, 2], SnmpValue::Integer(3)),
+        ];
+        let port_ids = vec![
+            (vec![0, 3, 1], text_value("eth0")),
+            (
+                vec![0, 5, 2],
+                SnmpValue::OctetString(vec![0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c]),
+            ),
+        ];
+        let addresses = vec![
+            (vec![0, 3, 1, 1, 4, 10, 0, 0, 21], SnmpValue::Integer(2)),
+            (vec![0, 5, 2, 1, 10, 0, 0, 22], SnmpValue::Integer(2)),
+        ];
+
+        let neighbors = parse_neighbors(names, subtypes, port_ids, addresses);
+
+        assert_eq!(
+            neighbors[&3],
+            vec![Neighbor {
+                name: Some("ap-lobby".to_string()),
+                port_id: Some("eth0".to_string()),
+                addresses: vec!["10.0.0.21".to_string()],
+            }]
+        );
+        assert_eq!(neighbors[&5][0].name, None);
+        assert_eq!(
+            neighbors[&5][0].port_id,
+            Some("00:1B:21:AA:BB:0C".to_string())
+        );
+        assert_eq!(neighbors[&5][0].addresses, vec!["10.0.0.22".to_string()]);
+    }
+
+    #[test]
+    fn test_map_local_ports() {
+        let port_ids = vec![
+            (vec![1], text_value("Gi1/0/1")),
+            (vec![2], text_value("00:1b:21:aa:bb:0c")),
+            (vec![3], text_value("uplink")),
+        ];
+        let port_descs = vec![
+            (vec![1], text_value("GigabitEthernet1/0/1")),
+            (vec![2], text_value("gi1/0/2")),
+            (vec![3], text_value("")),
+        ];
+        let if_names = vec![
+            (vec![10101], text_value("Gi1/0/1")),
+            (vec![10102], text_value("Gi1/0/2")),
+        ];
+
+        assert_eq!(
+            map_local_ports(port_ids, port_descs, if_names),
+            BTreeMap::from([(1, 10101), (2, 10102)])
+        );
+    }
+
+    #[test]
+    fn test_parse_address() {
+        let mut ipv6 = vec![2, 16, 0xfe, 0x80];
+        ipv6.extend([0; 13]);
+        ipv6.push(1);
+
+        assert_eq!(parse_address(&ipv6), Some("fe80::1".to_string()));
+        assert_eq!(parse_address(&[1, 3, 10, 0, 0]), None);
+        assert_eq!(parse_address(&[6, 6, 0, 1, 2, 3, 4, 5]), None);
+    }
+}
@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+// A MAC address read from any of the usual notations, aa:bb:cc:dd:ee:ff, aa-bb-cc-dd-ee-ff,
+// aabb.ccdd.eeff (Cisco) or aabbccddeeff, and stored as AA:BB:CC:DD:EE:FF
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MacAddress([u8; 6]);
+
+impl MacAddress {
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    // The group bit of the first octet, set on multicast and the broadcast address
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 6]
+    }
+
+    // Magic packets and forwarding tables are about a single device's own address
+    pub fn check_unicast(&self) -> Result<(), String> {
+        if self.is_zero() {
+            return Err(format!("{} is not a device's MAC address", self));
+        }
+
+        if self.is_multicast() {
+            return Err(format!(
+                "{} is a multicast MAC address, not a device's",
+                self
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Parses a device's MAC address as entered by a user
+    pub fn parse_unicast(input: &str) -> Result<Self, String> {
+        let mac = input.parse::<MacAddress>()?;
+        mac.check_unicast()?;
+
+        Ok(mac)
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid MAC address: {}, use AA:BB:CC:DD:EE:FF, AA-BB-CC-DD-EE-FF, AABB.CCDD.EEFF or AABBCCDDEEFF",
+                input
+            )
+        };
+
+        let trimmed = input.trim();
+        let groups: Vec<&str> = match [':', '-', '.'].into_iter().find(|c| trimmed.contains(*c)) {
+            Some(separator) => trimmed.split(separator).collect(),
+            None => vec![trimmed],
+        };
+
+        // Six groups of two digits, three of four or one of twelve
+        let size = match groups.len() {
+            1 | 3 | 6 => 12 / groups.len(),
+            _ => return Err(invalid()),
+        };
+        if groups
+            .iter()
+            .any(|group| group.len() != size || !group.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            return Err(invalid());
+        }
+
+        let digits = groups.concat();
+        let mut octets = [0u8; 6];
+        for (index, octet) in octets.iter_mut().enumerate() {
+            *octet =
+                u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+
+        Ok(Self(octets))
+    }
+}
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+}
+
+impl std::fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let octets: Vec<String> = self
+            .0
+            .iter()
+            .map(|octet| format!("{:02X}", octet))
+            .collect();
+        write!(f, "{}", octets.join(":"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OCTETS: [u8; 6] = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c];
+
+    #[test]
+    fn test_parse_notations() {
+        for input in [
+            "00:1b:21:aa:bb:0c",
+            "00-1B-21-AA-BB-0C",
+            "001b.21aa.bb0c",
+            "001B21AABB0C",
+            " 00:1B:21:AA:BB:0C ",
+        ] {
+            let mac = input.parse::<MacAddress>().unwrap();
+            assert_eq!(mac.octets(), OCTETS, "{}", input);
+            assert_eq!(mac.to_string(), "00:1B:21:AA:BB:0C");
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in [
+            "",
+            "00:1b:21:aa:bb",
+            "00:1b:21:aa:bb:0c:01",
+            "00:1b:21-aa:bb:0c",
+            "0:1b:21:aa:bb:0cc",
+            "001b.21aa.bb0",
+            "001b21aabb0g",
+            "+01b21aabb0c",
+            "ZZ:BB:CC:DD:EE:FF",
+        ] {
+            assert!(input.parse::<MacAddress>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_unicast() {
+        assert!(MacAddress::parse_unicast("00:1b:21:aa:bb:0c").is_ok());
+        assert!(MacAddress::parse_unicast("00:00:00:00:00:00").is_err());
+        assert!(MacAddress::parse_unicast("ff:ff:ff:ff:ff:ff").is_err());
+        assert!(MacAddress::parse_unicast("01:00:5e:00:00:fb").is_err());
+    }
+
+    #[test]
+    fn test_parse_uppercase_and_lowercase() {
+        assert_eq!(
+            "AA:BB:CC:DD:EE:FF".parse::<MacAddress>(),
+            "aa:bb:cc:dd:ee:ff".parse::<MacAddress>()
+        );
+        assert_eq!(
+            "aa:bb:cc:dd:ee:ff".parse::<MacAddress>().unwrap().octets(),
+            [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_parse_zeros_and_broadcast() {
+        assert_eq!(
+            "00:00:00:00:00:00".parse::<MacAddress>().unwrap().octets(),
+            [0; 6]
+        );
+        assert_eq!(
+            "FF:FF:FF:FF:FF:FF".parse::<MacAddress>().unwrap().octets(),
+            [0xFF; 6]
+        );
+    }
+
+    #[test]
+    fn test_display_from_octets() {
+        assert_eq!(MacAddress::from(OCTETS).to_string(), "00:1B:21:AA:BB:0C");
+    }
+}
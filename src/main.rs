@@ -1,8 +1,18 @@
+mod arp;
+mod bridge;
 mod config;
 mod device;
 mod errors;
+mod group;
+mod interface;
 mod keyring;
+mod lldp;
+mod mac;
+mod node;
+mod output;
+mod probe;
 mod rackcliargs;
+mod shutdown;
 mod snmp;
 mod snmpv2;
 mod snmpv3;
@@ -11,24 +21,76 @@ mod switch_oid;
 mod utils;
 mod wol;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use config::read_config;
+use group::GroupAction;
+use node::NodeAction;
 use openssl::provider::Provider;
-use rackcliargs::RackCliArgs;
+use output::OutputFormat;
+use rackcliargs::{
+    CycleArgs, GroupArgs, LocateArgs, NodeArgs, PortArgs, RackCliArgs, StatusArgs, SwitchArgs,
+    SwitchName, WolDisableArgs, WolEnableArgs, WolName, WolUpdateArgs,
+};
+use std::time::Duration;
 use switch::Switch;
-use wol::Wol;
+use utils::is_interactive;
+use wol::{Wol, WolSettings};
 
 // Add commands
-fn add_switch() {
+async fn add_switch(args: SwitchArgs, has_settings: bool) {
     let mut config = read_config();
-    let switch = Switch::create(config.get_switch_names());
+    let discover = args.discover;
+    let mut switch = if !has_settings && is_interactive() {
+        Switch::create(config.get_switch_names(), args.secrets()).await
+    } else {
+        match args
+            .read_stdin()
+            .and_then(|args| Switch::from_args(args, config.get_switch_names()))
+        {
+            Ok(switch) => switch,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    };
+
+    // A switch that cannot be reached is still added, discover can be run again later
+    if discover {
+        match switch.discover().await {
+            Ok(()) => println!("PoE ports on {}: {}", switch.name, switch.get_poe_ports()),
+            Err(e) => println!("Could not discover PoE ports: {}", e),
+        }
+    }
+
     config.add_switch(switch);
     config.write_config();
 }
 
-fn add_wol_device() {
+fn add_group(args: GroupArgs) {
+    let mut config = read_config();
+    match config.add_group(args.name, args.members) {
+        Ok(()) => config.write_config(),
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn add_node(args: NodeArgs) {
+    let mut config = read_config();
+    match config.add_node(args) {
+        Ok(()) => config.write_config(),
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn add_wol_device(settings: WolSettings) {
+    if let Err(e) = settings.validate() {
+        println!("{}", e);
+        return;
+    }
+
     let mut config = read_config();
-    let wol = Wol::create(config.get_wol_names());
+    let wol = Wol::create(config.get_wol_names(), settings);
     config.add_wol(wol);
     config.write_config();
 }
@@ -40,6 +102,22 @@ fn delete_switch() {
     config.write_config();
 }
 
+fn delete_group(name: String) {
+    let mut config = read_config();
+    match config.delete_group(name) {
+        Ok(()) => config.write_config(),
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn delete_node(name: String) {
+    let mut config = read_config();
+    match config.delete_node(name) {
+        Ok(()) => config.write_config(),
+        Err(e) => println!("{}", e),
+    }
+}
+
 fn delete_wol_device() {
     let mut config = read_config();
     config.delete_wol();
@@ -47,55 +125,174 @@ fn delete_wol_device() {
 }
 
 // List commands
-fn list_config() {
+fn list_config(format: OutputFormat) {
+    let config = read_config();
+    config.print_config(format);
+}
+
+fn list_switches(format: OutputFormat) {
+    let config = read_config();
+    config.print_switches(format);
+}
+
+fn list_wols(format: OutputFormat) {
     let config = read_config();
-    config.print_config();
+    config.print_wols(format);
 }
 
-fn list_switches() {
+fn list_groups(format: OutputFormat) {
     let config = read_config();
-    config.print_switches();
+    config.print_groups(format);
 }
 
-fn list_wols() {
+fn list_nodes(format: OutputFormat) {
     let config = read_config();
-    config.print_wols();
+    config.print_nodes(format);
 }
 
 // Update commands
-fn update_switch() {
+fn update_switch(args: SwitchArgs, has_settings: bool) {
     let mut config = read_config();
-    config.update_switch();
+    if !has_settings && is_interactive() {
+        config.update_switch(args.secrets());
+    } else if let Err(e) = args
+        .read_stdin()
+        .and_then(|args| config.update_switch_from_args(args))
+    {
+        println!("{}", e);
+        return;
+    }
     config.write_config();
 }
 
-fn update_wol_device() {
+fn update_node(name: String) {
     let mut config = read_config();
-    config.update_wol();
+    match config.update_node(name) {
+        Ok(()) => config.write_config(),
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn update_wol_device(args: WolUpdateArgs) {
+    if let Err(e) = args.settings.validate() {
+        println!("{}", e);
+        return;
+    }
+
+    let mut config = read_config();
+    config.update_wol(args.settings, args.reset);
     config.write_config();
 }
 
 // Enable commands
-async fn enable_switch() {
+async fn enable_switch(args: PortArgs, format: OutputFormat) {
     let mut config = read_config();
-    config.enable_switch().await;
+    config.enable_switch(args.switch, args.ports, format).await;
 }
 
-async fn enable_wol_device() {
+async fn enable_wol_device(args: WolEnableArgs) {
     let mut config = read_config();
-    config.enable_wol().await;
+    let wait = if args.wait {
+        Duration::from_secs(args.timeout)
+    } else {
+        Duration::ZERO
+    };
+
+    if !config
+        .enable_wol(args.name, wait, Duration::from_secs(args.interval))
+        .await
+    {
+        std::process::exit(1);
+    }
 }
 
 // Disable commands
-async fn disable_switch() {
+async fn disable_switch(args: PortArgs, format: OutputFormat) {
     let mut config = read_config();
-    config.disable_switch().await;
+    config.disable_switch(args.switch, args.ports, format).await;
+}
+
+async fn disable_wol_device(args: WolDisableArgs) {
+    let mut config = read_config();
+    if !config.disable_wol(args.name).await {
+        std::process::exit(1);
+    }
+}
+
+// Cycle commands
+async fn cycle_switch(args: CycleArgs, format: OutputFormat) {
+    let mut config = read_config();
+    config
+        .cycle_switch(
+            args.ports.switch,
+            args.ports.ports,
+            Duration::from_secs(args.delay),
+            format,
+        )
+        .await;
 }
 
 // Status commands
-async fn status_switch() {
+async fn status_switch(args: StatusArgs, format: OutputFormat) {
+    let mut config = read_config();
+    if config
+        .get_switch_status(args.ports.switch, args.ports.ports, args.interfaces, format)
+        .await
+    {
+        config.write_config();
+    }
+}
+
+async fn status_wol_device(args: WolName, format: OutputFormat) {
+    let config = read_config();
+    config.wol_status(args.name, format).await;
+}
+
+// Identify commands
+async fn identify_switch(args: SwitchName, format: OutputFormat) {
+    let mut config = read_config();
+    config.identify_switch(args.switch, format).await;
+}
+
+// Neighbors commands
+async fn neighbors_switch(args: PortArgs, format: OutputFormat) {
+    let mut config = read_config();
+    if config
+        .get_switch_neighbors(args.switch, args.ports, format)
+        .await
+    {
+        config.write_config();
+    }
+}
+
+// Discover commands
+async fn discover_switch(args: SwitchName) {
+    let mut config = read_config();
+    if config.discover_switch(args.switch).await {
+        config.write_config();
+    }
+}
+
+// Group commands
+async fn run_group(name: String, action: GroupAction, format: OutputFormat) {
+    let config = read_config();
+    config.run_group(name, action, format).await;
+}
+
+// Node commands
+async fn run_node(name: String, action: NodeAction, timeout: u64, format: OutputFormat) {
+    let config = read_config();
+    config
+        .run_node(name, action, Duration::from_secs(timeout), format)
+        .await;
+}
+
+// Locate commands
+async fn locate(args: LocateArgs, format: OutputFormat) {
     let mut config = read_config();
-    config.get_switch_status().await;
+    if config.locate(args.target, args.save, format).await {
+        config.write_config();
+    }
 }
 
 #[tokio::main]
@@ -105,27 +302,102 @@ async fn main() {
     let _default =
         Provider::load(None, "default").expect("Failed to load OpenSSL default provider");
 
-    let args = RackCliArgs::parse();
+    let matches = RackCliArgs::command().get_matches();
+    let args = RackCliArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let has_settings = rackcliargs::has_switch_settings(&matches);
 
     match args.device_type {
-        rackcliargs::DeviceType::List => list_config(),
+        rackcliargs::DeviceType::List => list_config(args.output),
         rackcliargs::DeviceType::Switch(switch) => match switch.command {
-            rackcliargs::SwitchSubCommand::Add => add_switch(),
+            rackcliargs::SwitchSubCommand::Add(args) => add_switch(args, has_settings).await,
             rackcliargs::SwitchSubCommand::Delete => delete_switch(),
-            rackcliargs::SwitchSubCommand::List => list_switches(),
-            rackcliargs::SwitchSubCommand::Update => update_switch(),
+            rackcliargs::SwitchSubCommand::List => list_switches(args.output),
+            rackcliargs::SwitchSubCommand::Update(args) => update_switch(args, has_settings),
             // Async calls
-            rackcliargs::SwitchSubCommand::Enable => enable_switch().await,
-            rackcliargs::SwitchSubCommand::Disable => disable_switch().await,
-            rackcliargs::SwitchSubCommand::Status => status_switch().await,
+            rackcliargs::SwitchSubCommand::Enable(port_args) => {
+                enable_switch(port_args, args.output).await
+            }
+            rackcliargs::SwitchSubCommand::Disable(port_args) => {
+                disable_switch(port_args, args.output).await
+            }
+            rackcliargs::SwitchSubCommand::Status(port_args) => {
+                status_switch(port_args, args.output).await
+            }
+            rackcliargs::SwitchSubCommand::Cycle(cycle_args) => {
+                cycle_switch(cycle_args, args.output).await
+            }
+            rackcliargs::SwitchSubCommand::Discover(switch_args) => {
+                discover_switch(switch_args).await
+            }
+            rackcliargs::SwitchSubCommand::Identify(switch_args) => {
+                identify_switch(switch_args, args.output).await
+            }
+            rackcliargs::SwitchSubCommand::Neighbors(port_args) => {
+                neighbors_switch(port_args, args.output).await
+            }
         },
         rackcliargs::DeviceType::Wol(wol) => match wol.command {
-            rackcliargs::WolSubCommand::Add => add_wol_device(),
+            rackcliargs::WolSubCommand::Add(settings) => add_wol_device(settings),
             rackcliargs::WolSubCommand::Delete => delete_wol_device(),
-            rackcliargs::WolSubCommand::List => list_wols(),
-            rackcliargs::WolSubCommand::Update => update_wol_device(),
+            rackcliargs::WolSubCommand::List => list_wols(args.output),
+            rackcliargs::WolSubCommand::Update(update_args) => update_wol_device(update_args),
+            // Async calls
+            rackcliargs::WolSubCommand::Enable(enable_args) => enable_wol_device(enable_args).await,
+            rackcliargs::WolSubCommand::Disable(disable_args) => {
+                disable_wol_device(disable_args).await
+            }
+            rackcliargs::WolSubCommand::Status(wol_args) => {
+                status_wol_device(wol_args, args.output).await
+            }
+        },
+        rackcliargs::DeviceType::Group(group) => match group.command {
+            rackcliargs::GroupSubCommand::Add(args) => add_group(args),
+            rackcliargs::GroupSubCommand::Delete(group) => delete_group(group.name),
+            rackcliargs::GroupSubCommand::List => list_groups(args.output),
+            // Async calls
+            rackcliargs::GroupSubCommand::Enable(group) => {
+                run_group(group.name, GroupAction::Enable, args.output).await
+            }
+            rackcliargs::GroupSubCommand::Disable(group) => {
+                run_group(group.name, GroupAction::Disable, args.output).await
+            }
+            rackcliargs::GroupSubCommand::Status(group) => {
+                run_group(group.name, GroupAction::Status, args.output).await
+            }
+            rackcliargs::GroupSubCommand::Cycle(cycle_args) => {
+                run_group(
+                    cycle_args.name,
+                    GroupAction::Cycle(Duration::from_secs(cycle_args.delay)),
+                    args.output,
+                )
+                .await
+            }
+        },
+        rackcliargs::DeviceType::Node(node) => match node.command {
+            rackcliargs::NodeSubCommand::Add(args) => add_node(args),
+            rackcliargs::NodeSubCommand::Delete(node) => delete_node(node.name),
+            rackcliargs::NodeSubCommand::List => list_nodes(args.output),
+            rackcliargs::NodeSubCommand::Update(node) => update_node(node.name),
             // Async calls
-            rackcliargs::WolSubCommand::Enable => enable_wol_device().await,
+            rackcliargs::NodeSubCommand::On(on_args) => {
+                run_node(on_args.name, NodeAction::On, on_args.timeout, args.output).await
+            }
+            rackcliargs::NodeSubCommand::Off(node) => {
+                run_node(node.name, NodeAction::Off, 0, args.output).await
+            }
+            rackcliargs::NodeSubCommand::Status(node) => {
+                run_node(node.name, NodeAction::Status, 0, args.output).await
+            }
+            rackcliargs::NodeSubCommand::Cycle(cycle_args) => {
+                run_node(
+                    cycle_args.on.name,
+                    NodeAction::Cycle(Duration::from_secs(cycle_args.delay)),
+                    cycle_args.on.timeout,
+                    args.output,
+                )
+                .await
+            }
         },
+        rackcliargs::DeviceType::Locate(locate_args) => locate(locate_args, args.output).await,
     }
 }
@@ -0,0 +1,447 @@
+use crate::device::Device;
+use crate::group::{member_switch, GroupMember};
+use crate::output::{self, OutputFormat};
+use crate::probe::{self, ProbeType};
+use crate::rackcliargs::NodeArgs;
+use crate::switch::{Switch, SwitchResult};
+use crate::wol::Wol;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// A machine powered from a switch port. Nodes with a Wol device are also woken through it
+// once powered, and nodes with a host are waited for until they answer on it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Node {
+    pub name: String,
+    // Switch and port the node is powered from, ex: core:14
+    port: String,
+    // Name of the [[wols]] entry the node is woken with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(
+        default = "probe::default_port",
+        skip_serializing_if = "probe::is_default_port"
+    )]
+    probe_port: u16,
+    #[serde(skip)]
+    switch: Option<Switch>,
+    #[serde(skip)]
+    wol_device: Option<Wol>,
+    #[serde(skip)]
+    timeout: Duration,
+    #[serde(skip)]
+    output: OutputFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeAction {
+    On,
+    Off,
+    Status,
+    Cycle(Duration),
+}
+
+// Node settings as shown by list
+#[derive(Serialize)]
+pub struct NodeRecord {
+    pub name: String,
+    pub port: String,
+    pub wol: Option<String>,
+    pub host: Option<String>,
+    pub probe_port: u16,
+}
+
+// The power and reachability of a node as shown by status
+#[derive(Serialize)]
+pub struct NodeStatus {
+    pub name: String,
+    pub switch: String,
+    pub port: String,
+    pub status: String,
+    pub detection: Option<String>,
+    pub power_mw: Option<u64>,
+    pub host: Option<String>,
+    pub reachable: Option<bool>,
+    pub latency_ms: Option<u128>,
+    pub error: Option<String>,
+}
+
+impl Node {
+    pub fn from_args(
+        args: NodeArgs,
+        switches: &[Switch],
+        wols: &[Wol],
+        node_names: Vec<String>,
+    ) -> Result<Self, String> {
+        if args.name.trim().is_empty() {
+            return Err("Name is required".to_string());
+        }
+
+        if node_names.contains(&args.name) {
+            return Err(format!("Node {} already exists", args.name));
+        }
+
+        let node = Self {
+            name: args.name,
+            port: args.port,
+            wol: args.wol,
+            host: args.host,
+            probe_port: args.probe_port,
+            switch: None,
+            wol_device: None,
+            timeout: Duration::ZERO,
+            output: OutputFormat::default(),
+        };
+        node.validate(switches, wols)?;
+
+        Ok(node)
+    }
+
+    // The port must be a single port on a configured switch, and the Wol device one that
+    // is configured
+    pub fn validate(&self, switches: &[Switch], wols: &[Wol]) -> Result<(), String> {
+        let member = self.port.parse::<GroupMember>()?;
+        let ports = member_switch(&member, switches)?.get_ports()?;
+        if ports.len() != 1 {
+            return Err(format!(
+                "A node is powered from one port, {} has {}",
+                self.port,
+                ports.len()
+            ));
+        }
+
+        if let Some(name) = &self.wol {
+            find_wol(name, wols)?;
+        }
+
+        if self
+            .host
+            .as_ref()
+            .is_some_and(|host| host.trim().is_empty())
+        {
+            return Err("Host cannot be empty".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Selects the node's port on a copy of its switch, loading the switch credentials, and
+    // copies its Wol device
+    pub(crate) fn attach(&mut self, switches: &[Switch], wols: &[Wol]) -> Result<(), String> {
+        let member = self.port.parse::<GroupMember>()?;
+        let mut switch = member_switch(&member, switches)?;
+        switch.prepare()?;
+        self.switch = Some(switch);
+
+        self.wol_device = match &self.wol {
+            Some(name) => Some(find_wol(name, wols)?.clone()),
+            None => None,
+        };
+
+        Ok(())
+    }
+
+    pub fn uses_switch(&self, name: &str) -> bool {
+        self.port
+            .parse::<GroupMember>()
+            .is_ok_and(|member| member.switch == name)
+    }
+
+    pub fn uses_wol(&self, name: &str) -> bool {
+        self.wol.as_deref() == Some(name)
+    }
+
+    pub(crate) fn select_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub(crate) fn select_output(&mut self, output: OutputFormat) {
+        self.output = output;
+    }
+
+    pub fn to_record(&self) -> NodeRecord {
+        NodeRecord {
+            name: self.name.clone(),
+            port: self.port.clone(),
+            wol: self.wol.clone(),
+            host: self.host.clone(),
+            probe_port: self.probe_port,
+        }
+    }
+
+    pub async fn run(&mut self, action: NodeAction) -> std::io::Result<()> {
+        match action {
+            NodeAction::On => self.enable().await,
+            NodeAction::Off => self.disable().await,
+            NodeAction::Status => {
+                self.status().await;
+                Ok(())
+            }
+            NodeAction::Cycle(delay) => self.cycle(delay).await,
+        }
+    }
+
+    pub async fn cycle(&mut self, delay: Duration) -> std::io::Result<()> {
+        let switch = self.get_switch()?;
+        let results = switch.power_cycle(delay).await?;
+        Node::check_results(&results)?;
+        output::print_notice(self.output, format!("Power cycled {}", self.port));
+
+        self.wake_up().await
+    }
+
+    fn get_switch(&mut self) -> std::io::Result<&mut Switch> {
+        self.switch.as_mut().ok_or_else(|| {
+            std::io::Error::other(format!("Node {} is not attached to its switch", self.name))
+        })
+    }
+
+    fn check_results(results: &[SwitchResult]) -> std::io::Result<()> {
+        match results.iter().find_map(|result| result.error.as_ref()) {
+            Some(e) => Err(std::io::Error::other(e.clone())),
+            None => Ok(()),
+        }
+    }
+
+    // Wakes the Wol device and waits for the host, the steps after powering the port. The
+    // device sends its own packet, with its SecureOn password and network settings, and
+    // the node does the waiting.
+    async fn wake_up(&mut self) -> std::io::Result<()> {
+        if let Some(wol) = &mut self.wol_device {
+            wol.select_wait(Duration::ZERO, Duration::ZERO);
+            wol.enable().await?;
+        }
+
+        let Some(host) = &self.host else {
+            return Ok(());
+        };
+        if self.timeout.is_zero() {
+            return Ok(());
+        }
+
+        output::print_notice(
+            self.output,
+            format!(
+                "Waiting up to {}s for {}:{}",
+                self.timeout.as_secs(),
+                host,
+                self.probe_port
+            ),
+        );
+        match probe::wait_until_up(host, ProbeType::Tcp, self.probe_port, self.timeout).await {
+            Ok(elapsed) => {
+                output::print_notice(
+                    self.output,
+                    format!("{} is up after {}s", self.name, elapsed.as_secs()),
+                );
+                Ok(())
+            }
+            Err(e) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "{} did not come up within {}s: {}",
+                    self.name,
+                    self.timeout.as_secs(),
+                    e
+                ),
+            )),
+        }
+    }
+}
+
+fn find_wol<'a>(name: &str, wols: &'a [Wol]) -> Result<&'a Wol, String> {
+    wols.iter()
+        .find(|wol| wol.name == name)
+        .ok_or_else(|| format!("Wol device {} not found", name))
+}
+
+impl Device for Node {
+    async fn disable(&mut self) -> std::io::Result<()> {
+        let switch = self.get_switch()?;
+        let results = switch.power_off().await?;
+        Node::check_results(&results)?;
+
+        output::print_notice(self.output, format!("Powered off {}", self.port));
+        Ok(())
+    }
+
+    async fn enable(&mut self) -> std::io::Result<()> {
+        let switch = self.get_switch()?;
+        let results = switch.power_on().await?;
+        Node::check_results(&results)?;
+        output::print_notice(self.output, format!("Powered on {}", self.port));
+
+        self.wake_up().await
+    }
+
+    async fn status(&mut self) {
+        let results = match self.get_switch() {
+            Ok(switch) => switch.port_status().await,
+            Err(e) => Err(e),
+        };
+        let port = match results.map(|results| results.into_iter().next()) {
+            Ok(Some(port)) => port,
+            Ok(None) => return,
+            Err(e) => {
+                output::print_notice(self.output, e);
+                return;
+            }
+        };
+
+        let probe = match &self.host {
+            Some(host) => Some(probe::tcp_probe(host, self.probe_port).await),
+            None => None,
+        };
+
+        if self.output != OutputFormat::Table {
+            let status = NodeStatus {
+                name: self.name.clone(),
+                switch: port.switch.clone(),
+                port: port.get_port().to_string(),
+                status: port.status.clone(),
+                detection: port.detection.clone(),
+                power_mw: port.power_mw,
+                error: port.error.clone(),
+                host: self.host.clone(),
+                reachable: probe.as_ref().map(Result::is_ok),
+                latency_ms: probe
+                    .and_then(Result::ok)
+                    .map(|latency| latency.as_millis()),
+            };
+            output::print_records(self.output, &[status]);
+            return;
+        }
+
+        println!("Status for {}:", self.name);
+        println!("\t{}", port);
+        if let (Some(host), Some(probe)) = (&self.host, probe) {
+            match probe {
+                Ok(latency) => println!(
+                    "\tHost: {}:{} up, {} ms",
+                    host,
+                    self.probe_port,
+                    latency.as_millis()
+                ),
+                Err(e) => println!("\tHost: {}:{} down, {}", host, self.probe_port, e),
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        self.port = dialoguer::Input::<String>::new()
+            .with_prompt("Port (SWITCH:PORT)")
+            .default(self.port.clone())
+            .validate_with(|input: &String| input.parse::<GroupMember>().map(|_| ()))
+            .interact()
+            .unwrap();
+
+        let wol = dialoguer::Input::<String>::new()
+            .with_prompt("Wol device (empty for none)")
+            .default(self.wol.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact()
+            .unwrap();
+        self.wol = (!wol.trim().is_empty()).then(|| wol.trim().to_string());
+
+        let host = dialoguer::Input::<String>::new()
+            .with_prompt("Host (empty for none)")
+            .default(self.host.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact()
+            .unwrap();
+        self.host = (!host.trim().is_empty()).then(|| host.trim().to_string());
+
+        self.probe_port = dialoguer::Input::<u16>::new()
+            .with_prompt("Probe port")
+            .default(self.probe_port)
+            .interact()
+            .unwrap();
+    }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "  Name: {}\n  Port: {}\n", self.name, self.port)?;
+
+        if let Some(wol) = &self.wol {
+            writeln!(f, "  Wol: {}", wol)?;
+        }
+
+        if let Some(host) = &self.host {
+            writeln!(f, "  Host: {}:{}", host, self.probe_port)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::switch::tests::switches;
+    use crate::wol::tests::wol;
+    use crate::wol::WolSettings;
+    use std::net::UdpSocket;
+
+    fn node(port: &str, wol_name: Option<&str>) -> Result<Node, String> {
+        let args = NodeArgs {
+            name: "pi-1".to_string(),
+            port: port.to_string(),
+            wol: wol_name.map(str::to_string),
+            host: Some("10.0.0.50".to_string()),
+            probe_port: 22,
+        };
+        Node::from_args(args, &switches(), &[wol("00:1B:21:AA:BB:0C")], vec![])
+    }
+
+    #[test]
+    fn test_new_node_valid() {
+        let node = node("core:14", Some("test")).unwrap();
+
+        assert_eq!(
+            node.to_string(),
+            "  Name: pi-1\n  Port: core:14\n  Wol: test\n  Host: 10.0.0.50:22\n"
+        );
+    }
+
+    #[test]
+    fn test_new_node_invalid() {
+        assert_eq!(
+            node("edge:14", None).err(),
+            Some("Switch edge not found".to_string())
+        );
+        assert_eq!(
+            node("core:1-2", None).err(),
+            Some("A node is powered from one port, core:1-2 has 2".to_string())
+        );
+        assert_eq!(
+            node("core:14", Some("nas")).err(),
+            Some("Wol device nas not found".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wake_up_with_wol_settings() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        device.apply_settings(
+            WolSettings {
+                unicast: Some("127.0.0.1".parse().unwrap()),
+                port: Some(listener.local_addr().unwrap().port()),
+                ..Default::default()
+            },
+            false,
+        );
+
+        let mut node = node("core:14", Some("test")).unwrap();
+        node.wol_device = Some(device);
+        node.wake_up().await.unwrap();
+
+        let mut packet = [0; 128];
+        let (length, _) = listener.recv_from(&mut packet).unwrap();
+        assert_eq!(length, 102);
+        assert_eq!(&packet[6..12], &[0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c]);
+    }
+}
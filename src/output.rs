@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(ValueEnum, PartialEq, Clone, Copy, Debug, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    Csv,
+}
+
+// Serializes a single value, used where the output holds more than one kind of record
+pub fn render_value<T: Serialize>(format: OutputFormat, value: &T) -> Result<String, String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+        OutputFormat::Csv | OutputFormat::Table => {
+            Err(format!("{:?} output is not supported here", format))
+        }
+    }
+}
+
+pub fn render_records<T: Serialize>(format: OutputFormat, records: &[T]) -> Result<String, String> {
+    match format {
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for record in records {
+                writer.serialize(record).map_err(|e| e.to_string())?;
+            }
+
+            let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+            String::from_utf8(bytes).map_err(|e| e.to_string())
+        }
+        _ => render_value(format, &records),
+    }
+}
+
+pub fn print_value<T: Serialize>(format: OutputFormat, value: &T) {
+    match render_value(format, value) {
+        Ok(output) => print!("{}", with_newline(output)),
+        Err(e) => eprintln!("Error formatting output: {}", e),
+    }
+}
+
+pub fn print_records<T: Serialize>(format: OutputFormat, records: &[T]) {
+    match render_records(format, records) {
+        Ok(output) => print!("{}", with_newline(output)),
+        Err(e) => eprintln!("Error formatting output: {}", e),
+    }
+}
+
+// Messages that are not part of the output, kept off stdout when it is read by a program
+pub fn print_notice(format: OutputFormat, notice: impl std::fmt::Display) {
+    match format {
+        OutputFormat::Table => println!("{}", notice),
+        _ => eprintln!("{}", notice),
+    }
+}
+
+fn with_newline(mut output: String) -> String {
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        name: String,
+        port: u64,
+        error: Option<String>,
+    }
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record {
+                name: "core".to_string(),
+                port: 1,
+                error: None,
+            },
+            Record {
+                name: "core".to_string(),
+                port: 2,
+                error: Some("timeout".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_records_json() {
+        let output = render_records(OutputFormat::Json, &records()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value[0]["port"], 1);
+        assert_eq!(value[1]["error"], "timeout");
+    }
+
+    #[test]
+    fn test_render_records_yaml() {
+        let output = render_records(OutputFormat::Yaml, &records()).unwrap();
+        assert!(output.contains("- name: core\n  port: 1\n  error: null\n"));
+    }
+
+    #[test]
+    fn test_render_records_csv() {
+        let output = render_records(OutputFormat::Csv, &records()).unwrap();
+        assert_eq!(output, "name,port,error\ncore,1,\ncore,2,timeout\n");
+    }
+
+    #[test]
+    fn test_render_value_csv_unsupported() {
+        assert!(render_value(OutputFormat::Csv, &records()).is_err());
+    }
+}
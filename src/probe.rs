@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+// Time between attempts while waiting for a host to come up
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+// Time a single attempt may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// ICMP echo types, request and reply
+const ICMPV4_ECHO: (u8, u8) = (8, 0);
+const ICMPV6_ECHO: (u8, u8) = (128, 129);
+
+pub fn default_port() -> u16 {
+    22
+}
+
+pub fn is_default_port(port: &u16) -> bool {
+    *port == default_port()
+}
+
+// How a host is checked for being up
+#[derive(clap::ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeType {
+    /// Connect to a TCP port
+    #[default]
+    Tcp,
+    /// Send an ICMP echo request (ping)
+    Icmp,
+}
+
+impl ProbeType {
+    pub fn is_default(&self) -> bool {
+        *self == ProbeType::default()
+    }
+
+    // The host and port, or the host and how it is pinged
+    pub fn describe(&self, host: &str, port: u16) -> String {
+        match self {
+            ProbeType::Tcp => format!("{}:{}", host, port),
+            ProbeType::Icmp => format!("{} (ICMP echo)", host),
+        }
+    }
+}
+
+impl std::fmt::Display for ProbeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeType::Tcp => write!(f, "TCP connect"),
+            ProbeType::Icmp => write!(f, "ICMP echo"),
+        }
+    }
+}
+
+// Checks the host the given way, returning how long it took to answer
+pub async fn probe(host: &str, probe_type: ProbeType, port: u16) -> Result<Duration, String> {
+    match probe_type {
+        ProbeType::Tcp => tcp_probe(host, port).await,
+        ProbeType::Icmp => icmp_probe(host).await,
+    }
+}
+
+// Opens a TCP connection to the host and returns how long it took. A refused connection
+// is an answer from the host, so it counts as up.
+pub async fn tcp_probe(host: &str, port: u16) -> Result<Duration, String> {
+    let start = Instant::now();
+
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(start.elapsed()),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => Ok(start.elapsed()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("No answer within {}s", PROBE_TIMEOUT.as_secs())),
+    }
+}
+
+// Probes the host the given way until it answers or the timeout runs out, returning how
+// long it took
+pub async fn wait_until_up(
+    host: &str,
+    probe_type: ProbeType,
+    port: u16,
+    timeout: Duration,
+) -> Result<Duration, String> {
+    let start = Instant::now();
+
+    loop {
+        match probe(host, probe_type, port).await {
+            Ok(_) => return Ok(start.elapsed()),
+            Err(e) if start.elapsed() + PROBE_INTERVAL >= timeout => return Err(e),
+            Err(_) => tokio::time::sleep(PROBE_INTERVAL).await,
+        }
+    }
+}
+
+// Pings the host and returns how long the echo reply took
+pub async fn icmp_probe(host: &str) -> Result<Duration, String> {
+    let host = host.to_string();
+    tokio::task::spawn_blocking(move || ping(&host))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn ping(host: &str) -> Result<Duration, String> {
+    let address = (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| format!("{} has no address", host))?;
+    let (domain, protocol, (request, reply)) = match address {
+        SocketAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, ICMPV4_ECHO),
+        SocketAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, ICMPV6_ECHO),
+    };
+
+    // Unprivileged ICMP sockets need the user's group in net.ipv4.ping_group_range, raw
+    // sockets need CAP_NET_RAW. Raw IPv4 sockets also get the IP header.
+    let (socket, raw) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+        Ok(socket) => (socket, false),
+        Err(_) => (
+            Socket::new(domain, Type::RAW, Some(protocol))
+                .map_err(|e| format!("Cannot open an ICMP socket: {}", e))?,
+            true,
+        ),
+    };
+    let socket: UdpSocket = socket.into();
+
+    // Unprivileged sockets replace the identifier with their own
+    let identifier = std::process::id() as u16;
+    let sequence = 1;
+    let start = Instant::now();
+    socket
+        .send_to(&echo_request(request, identifier, sequence), address)
+        .map_err(|e| e.to_string())?;
+
+    // Other echo replies can arrive first, so each read only waits for what is left
+    let no_answer = || format!("No answer within {}s", PROBE_TIMEOUT.as_secs());
+    let deadline = start + PROBE_TIMEOUT;
+    let mut buffer = [0u8; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(no_answer());
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .map_err(|e| e.to_string())?;
+
+        let (length, from) = socket.recv_from(&mut buffer).map_err(|e| match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => no_answer(),
+            _ => e.to_string(),
+        })?;
+
+        let header = match (raw, address) {
+            (true, SocketAddr::V4(_)) => ((buffer[0] & 0x0f) as usize) * 4,
+            _ => 0,
+        };
+        let message = &buffer[header.min(length)..length];
+
+        if from.ip() == address.ip()
+            && message.len() >= 8
+            && message[0] == reply
+            && (!raw || message[4..6] == identifier.to_be_bytes())
+            && message[6..8] == sequence.to_be_bytes()
+        {
+            return Ok(start.elapsed());
+        }
+    }
+}
+
+// An echo request with the checksum filled in, which the kernel does for ICMPv6
+fn echo_request(request: u8, identifier: u16, sequence: u16) -> Vec<u8> {
+    let mut message = vec![request, 0, 0, 0];
+    message.extend(identifier.to_be_bytes());
+    message.extend(sequence.to_be_bytes());
+    message.extend(b"rackcli");
+
+    let checksum = checksum(&message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    message
+}
+
+// The internet checksum, the ones' complement of the ones' complement sum of 16 bit words
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    // Connecting or sending to the broadcast address fails at once, without a DNS lookup.
+    // A closed port would not do, since a refused connection counts as up.
+    pub(crate) const UNREACHABLE_HOST: &str = "255.255.255.255";
+
+    #[tokio::test]
+    async fn test_tcp_probe_listening_and_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(tcp_probe("127.0.0.1", port).await.is_ok());
+
+        // Nothing listens once the listener is dropped, which the host answers with a reset
+        drop(listener);
+        assert!(tcp_probe("127.0.0.1", port).await.is_ok());
+    }
+
+    #[test]
+    fn test_echo_request_checksum() {
+        let message = echo_request(ICMPV4_ECHO.0, 0x1234, 1);
+
+        assert_eq!(&message[..2], &[8, 0]);
+        assert_eq!(&message[4..8], &[0x12, 0x34, 0, 1]);
+        // A message with its checksum filled in sums to zero
+        assert_eq!(checksum(&message), 0);
+    }
+
+    #[tokio::test]
+    async fn test_icmp_probe_unreachable() {
+        let result = probe(UNREACHABLE_HOST, ProbeType::Icmp, 0).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_up_unreachable() {
+        let result = wait_until_up(UNREACHABLE_HOST, ProbeType::Tcp, 22, Duration::ZERO).await;
+        assert!(result.is_err());
+    }
+}
@@ -1,18 +1,37 @@
-use clap::{Args, Parser, Subcommand};
+use crate::output::OutputFormat;
+use crate::snmp::SnmpSettings;
+use crate::switch::{PortLabel, SNMPAuth, SNMPEncryption, SNMPVersion, SwitchPort, SwitchSecrets};
+use crate::wol::WolSettings;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, Id, Parser, Subcommand};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::io::Read;
 
 #[derive(Parser, Debug)]
 #[clap(author, about, version)]
 pub struct RackCliArgs {
     #[clap(subcommand)]
     pub device_type: DeviceType,
+    /// Output format for list and status commands
+    #[clap(short, long, value_enum, global = true, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
 }
 
+// Parsed once at startup, so the size of the largest command does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum DeviceType {
     /// Add, Delete, List, Update, Enable, Disable, or get Status for Switch devices
     Switch(SwitchCmd),
     /// Add, Delete, List, Update, or Enable Wake-On-Lan devices
     Wol(WolCmd),
+    /// Add, Delete, List, Enable, Disable, Cycle or get Status for groups of switch ports
+    Group(GroupCmd),
+    /// Add, Delete, List, Update, power On, Off, Cycle or get Status for nodes
+    Node(NodeCmd),
+    /// Find the switch port a MAC address or Wake-On-Lan device is on
+    Locate(LocateArgs),
     /// List all devices
     List,
 }
@@ -26,19 +45,226 @@ pub struct SwitchCmd {
 #[derive(Subcommand, Debug)]
 pub enum SwitchSubCommand {
     /// Add a new Switch device
-    Add,
+    Add(SwitchArgs),
     /// Delete a Switch device
     Delete,
     /// List all Switch devices
     List,
     /// Update a Switch device
-    Update,
+    Update(SwitchArgs),
     /// Enable Switch device
-    Enable,
+    Enable(PortArgs),
     /// Disable Switch device
-    Disable,
+    Disable(PortArgs),
     /// Get status of Switch device
-    Status,
+    Status(StatusArgs),
+    /// Power cycle ports on a Switch device
+    Cycle(CycleArgs),
+    /// Find the PoE ports of a Switch device and save them
+    Discover(SwitchName),
+    /// Show the brand and description the Switch device reports
+    Identify(SwitchName),
+    /// Show the LLDP neighbors on the ports of a Switch device with their PoE status
+    Neighbors(PortArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SwitchName {
+    /// Name of the switch, prompted for if omitted
+    #[clap(short, long)]
+    pub switch: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct PortArgs {
+    /// Name of the switch, prompted for if omitted
+    #[clap(short, long)]
+    pub switch: Option<String>,
+    /// List of ports (ex: 1-6,8,10-12), prompted for if omitted
+    #[clap(short, long)]
+    pub ports: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    #[clap(flatten)]
+    pub ports: PortArgs,
+    /// Show the interface name, description, link state and speed from IF-MIB
+    #[clap(long)]
+    pub interfaces: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CycleArgs {
+    #[clap(flatten)]
+    pub ports: PortArgs,
+    /// Seconds to wait between powering the ports off and on
+    #[clap(short, long, default_value_t = 5)]
+    pub delay: u64,
+}
+
+/// Switch settings for non-interactive add and update. Each value can come from a flag,
+/// a RACKCLI_* environment variable or a TOML/JSON document read with --stdin, in that
+/// order of precedence. The document uses the same keys as config.toml.
+#[derive(Args, Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchArgs {
+    /// Name of the switch, used to find the switch on update
+    #[clap(long, env = "RACKCLI_NAME")]
+    pub name: Option<String>,
+    /// IP address or hostname of the switch
+    #[clap(long, env = "RACKCLI_IP")]
+    pub ip: Option<String>,
+    /// Switch brand
+    #[clap(long, env = "RACKCLI_BRAND")]
+    pub brand: Option<String>,
+    /// Number of ports
+    #[clap(long, env = "RACKCLI_PORTS")]
+    pub ports: Option<u64>,
+    /// Number of members of a stacked switch
+    #[clap(long, env = "RACKCLI_STACK_MEMBERS")]
+    pub stack_members: Option<u64>,
+    /// SNMP version
+    #[clap(
+        long = "snmp-version",
+        value_name = "VERSION",
+        value_enum,
+        env = "RACKCLI_SNMP_VERSION"
+    )]
+    pub version: Option<SNMPVersion>,
+    /// Store passwords in the system keystore
+    #[clap(long, env = "RACKCLI_KEYRING")]
+    pub keyring: Option<bool>,
+    /// SNMP v2 community string
+    #[clap(long, env = "RACKCLI_COMMUNITY", hide_env_values = true)]
+    pub community: Option<String>,
+    /// SNMP v3 authentication protocol
+    #[clap(long, value_enum, env = "RACKCLI_AUTH")]
+    pub auth: Option<SNMPAuth>,
+    /// SNMP v3 username
+    #[clap(long = "username", value_name = "USERNAME", env = "RACKCLI_USERNAME")]
+    pub auth_user: Option<String>,
+    /// SNMP v3 authentication password
+    #[clap(
+        long = "auth-password",
+        value_name = "PASSWORD",
+        env = "RACKCLI_AUTH_PASSWORD",
+        hide_env_values = true
+    )]
+    pub auth_pass: Option<String>,
+    /// SNMP v3 encryption protocol
+    #[clap(long, value_enum, env = "RACKCLI_ENCRYPTION")]
+    pub encryption: Option<SNMPEncryption>,
+    /// SNMP v3 encryption password
+    #[clap(
+        long = "encryption-password",
+        value_name = "PASSWORD",
+        env = "RACKCLI_ENCRYPTION_PASSWORD",
+        hide_env_values = true
+    )]
+    pub encryption_pass: Option<String>,
+    /// Label a port, repeat for more labels (ex: 3=lobby-camera or 2/5=ap-2), an empty label removes it
+    #[clap(long = "label", value_name = "PORT=LABEL")]
+    #[serde(deserialize_with = "deserialize_labels")]
+    pub labels: Vec<PortLabel>,
+    #[clap(flatten)]
+    pub snmp: SnmpSettings,
+    /// Read settings from a TOML or JSON document on stdin
+    #[clap(long)]
+    #[serde(skip)]
+    pub stdin: bool,
+    /// Find the PoE ports of the switch once it is added
+    #[clap(long)]
+    #[serde(skip)]
+    pub discover: bool,
+}
+
+// Flags of switch add and update that can be combined with the prompts, the secrets are
+// used instead of asking for them
+const PROMPT_FLAGS: [&str; 4] = ["community", "auth_pass", "encryption_pass", "discover"];
+
+// Whether switch add or update was given a setting on the command line. Values read from
+// RACKCLI_* variables do not count, so an exported variable does not turn off the prompts,
+// and neither do secrets or --discover.
+pub fn has_switch_settings(matches: &ArgMatches) -> bool {
+    let Some((_, command)) = matches
+        .subcommand_matches("switch")
+        .and_then(ArgMatches::subcommand)
+    else {
+        return false;
+    };
+
+    command
+        .ids()
+        .map(Id::as_str)
+        .filter(|id| !PROMPT_FLAGS.contains(id))
+        .any(|id| command.value_source(id) == Some(ValueSource::CommandLine))
+}
+
+impl SwitchArgs {
+    pub fn secrets(&self) -> SwitchSecrets {
+        SwitchSecrets {
+            community: self.community.clone(),
+            auth_pass: self.auth_pass.clone(),
+            encryption_pass: self.encryption_pass.clone(),
+        }
+    }
+
+    pub fn read_stdin(self) -> Result<Self, String> {
+        if !self.stdin {
+            return Ok(self);
+        }
+
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| format!("Error reading stdin: {}", e))?;
+
+        Ok(self.merge(SwitchArgs::parse_document(&input)?))
+    }
+
+    fn parse_document(input: &str) -> Result<Self, String> {
+        if input.trim_start().starts_with('{') {
+            serde_json::from_str(input).map_err(|e| format!("Invalid JSON document: {}", e))
+        } else {
+            toml::from_str(input).map_err(|e| format!("Invalid TOML document: {}", e))
+        }
+    }
+
+    // Values already set on self take precedence over the document
+    fn merge(self, document: SwitchArgs) -> Self {
+        Self {
+            name: self.name.or(document.name),
+            ip: self.ip.or(document.ip),
+            brand: self.brand.or(document.brand),
+            ports: self.ports.or(document.ports),
+            stack_members: self.stack_members.or(document.stack_members),
+            version: self.version.or(document.version),
+            keyring: self.keyring.or(document.keyring),
+            community: self.community.or(document.community),
+            auth: self.auth.or(document.auth),
+            auth_user: self.auth_user.or(document.auth_user),
+            auth_pass: self.auth_pass.or(document.auth_pass),
+            encryption: self.encryption.or(document.encryption),
+            encryption_pass: self.encryption_pass.or(document.encryption_pass),
+            labels: [document.labels, self.labels].concat(),
+            snmp: self.snmp.or(document.snmp),
+            stdin: self.stdin,
+            discover: self.discover,
+        }
+    }
+}
+
+// The document uses the config.toml layout for labels, a table of port = "label"
+fn deserialize_labels<'de, D>(deserializer: D) -> Result<Vec<PortLabel>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let labels = BTreeMap::<SwitchPort, String>::deserialize(deserializer)?;
+    Ok(labels
+        .into_iter()
+        .map(|(port, label)| PortLabel { port, label })
+        .collect())
 }
 
 #[derive(Args, Debug)]
@@ -50,13 +276,284 @@ pub struct WolCmd {
 #[derive(Subcommand, Debug)]
 pub enum WolSubCommand {
     /// Add a new Wake-On-Lan device
-    Add,
+    Add(WolSettings),
     /// Delete a Wake-On-Lan device
     Delete,
     /// List all Wake-On-Lan devices
     List,
     /// Update a Wake-On-Lan device
-    Update,
+    Update(WolUpdateArgs),
     /// Enable Wake-On-Lan device
-    Enable,
+    Enable(WolEnableArgs),
+    /// Shut down or put a Wake-On-Lan device to sleep
+    Disable(WolDisableArgs),
+    /// Get whether Wake-On-Lan devices are up and seen on the LAN
+    Status(WolName),
+}
+
+#[derive(Args, Debug)]
+pub struct WolUpdateArgs {
+    #[clap(flatten)]
+    pub settings: WolSettings,
+    /// Clear the device's own network settings, so the [wol] ones apply, before applying any given here
+    #[clap(long)]
+    pub reset: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct WolDisableArgs {
+    /// Name of the Wake-On-Lan device, prompted for when left out
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct WolName {
+    /// Name of the Wake-On-Lan device, all devices when left out
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct WolEnableArgs {
+    /// Name of the Wake-On-Lan device, prompted for when left out
+    pub name: Option<String>,
+    /// Wait for the device's host to answer, resending the magic packet until it does
+    #[clap(short, long)]
+    pub wait: bool,
+    /// Seconds to wait for the host with --wait
+    #[clap(short, long, default_value_t = 120, requires = "wait")]
+    pub timeout: u64,
+    /// Seconds between magic packets with --wait
+    #[clap(short, long, default_value_t = 10, requires = "wait")]
+    pub interval: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct NodeCmd {
+    #[clap(subcommand)]
+    pub command: NodeSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NodeSubCommand {
+    /// Add a new node powered from a switch port
+    Add(NodeArgs),
+    /// Delete a node
+    Delete(NodeName),
+    /// List all nodes
+    List,
+    /// Update a node
+    Update(NodeName),
+    /// Power on a node, wake it and wait for it to come up
+    On(NodeOnArgs),
+    /// Power off a node
+    Off(NodeName),
+    /// Get the port status of a node and whether it is up
+    Status(NodeName),
+    /// Power cycle a node, wake it and wait for it to come up
+    Cycle(NodeCycleArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct NodeName {
+    /// Name of the node
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct NodeArgs {
+    /// Name of the node
+    pub name: String,
+    /// Switch port the node is powered from (ex: core:14)
+    #[clap(short, long, value_name = "SWITCH:PORT")]
+    pub port: String,
+    /// Wol device to wake after powering the port, by name
+    #[clap(short, long, value_name = "NAME")]
+    pub wol: Option<String>,
+    /// IP or hostname to wait for after powering the port
+    #[clap(long)]
+    pub host: Option<String>,
+    /// TCP port probed on the host to see if it is up
+    #[clap(long, default_value_t = 22)]
+    pub probe_port: u16,
+}
+
+#[derive(Args, Debug)]
+pub struct NodeOnArgs {
+    /// Name of the node
+    pub name: String,
+    /// Seconds to wait for the host to come up, 0 to not wait
+    #[clap(short, long, default_value_t = 120)]
+    pub timeout: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct NodeCycleArgs {
+    #[clap(flatten)]
+    pub on: NodeOnArgs,
+    /// Seconds to wait between powering the port off and on
+    #[clap(short, long, default_value_t = 5)]
+    pub delay: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct LocateArgs {
+    /// MAC address (XX:XX:XX:XX:XX:XX) or name of a Wake-On-Lan device
+    pub target: String,
+    /// Save the port the device was found on with its Wake-On-Lan device
+    #[clap(long)]
+    pub save: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct GroupCmd {
+    #[clap(subcommand)]
+    pub command: GroupSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GroupSubCommand {
+    /// Add a new group of switch ports
+    Add(GroupArgs),
+    /// Delete a group
+    Delete(GroupName),
+    /// List all groups
+    List,
+    /// Enable all ports in a group
+    Enable(GroupName),
+    /// Disable all ports in a group
+    Disable(GroupName),
+    /// Get status of all ports in a group
+    Status(GroupName),
+    /// Power cycle all ports in a group
+    Cycle(GroupCycleArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct GroupName {
+    /// Name of the group
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct GroupArgs {
+    /// Name of the group
+    pub name: String,
+    /// Member as SWITCH:PORTS (ex: core:1-4,lobby-camera), repeat for each member
+    #[clap(short, long = "member", value_name = "SWITCH:PORTS", required = true)]
+    pub members: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct GroupCycleArgs {
+    /// Name of the group
+    pub name: String,
+    /// Seconds to wait between powering the ports off and on
+    #[clap(short, long, default_value_t = 5)]
+    pub delay: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_parse_document_toml() {
+        let args = SwitchArgs::parse_document(
+            "name = \"core\"\nip = \"10.0.0.2\"\nports = 24\nversion = \"V2\"\n",
+        )
+        .unwrap();
+        assert_eq!(args.name, Some("core".to_string()));
+        assert_eq!(args.ip, Some("10.0.0.2".to_string()));
+        assert_eq!(args.ports, Some(24));
+        assert_eq!(args.version, Some(SNMPVersion::V2));
+    }
+
+    #[test]
+    fn test_parse_document_snmp_settings() {
+        let args = SwitchArgs::parse_document(
+            "name = \"core\"\n[snmp]\nport = 1161\ntimeout = 2\nretries = 3\n",
+        )
+        .unwrap();
+        assert_eq!(args.snmp.port, Some(1161));
+        assert_eq!(args.snmp.timeout, Some(2));
+        assert_eq!(args.snmp.retries, Some(3));
+    }
+
+    #[test]
+    fn test_parse_document_json() {
+        let args =
+            SwitchArgs::parse_document(r#"{"name": "core", "auth": "Sha256", "keyring": false}"#)
+                .unwrap();
+        assert_eq!(args.name, Some("core".to_string()));
+        assert_eq!(args.auth, Some(SNMPAuth::Sha256));
+        assert_eq!(args.keyring, Some(false));
+    }
+
+    #[test]
+    fn test_parse_document_labels() {
+        let args = SwitchArgs::parse_document("[labels]\n3 = \"lobby-camera\"\n").unwrap();
+        assert_eq!(
+            args.labels,
+            vec![PortLabel {
+                port: SwitchPort::from(3),
+                label: "lobby-camera".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_document_unknown_key() {
+        assert!(SwitchArgs::parse_document("colour = \"red\"").is_err());
+    }
+
+    fn switch_settings(args: &[&str]) -> bool {
+        let matches = RackCliArgs::command()
+            .try_get_matches_from([&["rackcli", "switch"], args].concat())
+            .unwrap();
+        has_switch_settings(&matches)
+    }
+
+    #[test]
+    fn test_has_switch_settings() {
+        assert!(!switch_settings(&["add"]));
+        assert!(!switch_settings(&["add", "--discover"]));
+        assert!(!switch_settings(&["update", "--community", "public"]));
+        assert!(switch_settings(&["add", "--ip", "10.0.0.2"]));
+        assert!(switch_settings(&["update", "--timeout", "3"]));
+        assert!(switch_settings(&["add", "--stdin"]));
+        assert!(!switch_settings(&["list"]));
+    }
+
+    #[test]
+    fn test_secrets() {
+        let args = SwitchArgs::parse_document(
+            "community = \"public\"\nauth_pass = \"secret\"\nip = \"10.0.0.2\"",
+        )
+        .unwrap();
+        assert_eq!(
+            args.secrets(),
+            SwitchSecrets {
+                community: Some("public".to_string()),
+                auth_pass: Some("secret".to_string()),
+                encryption_pass: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_merge_prefers_flags() {
+        let flags = SwitchArgs {
+            ip: Some("10.0.0.3".to_string()),
+            ..Default::default()
+        };
+        let document = SwitchArgs {
+            name: Some("core".to_string()),
+            ip: Some("10.0.0.2".to_string()),
+            ..Default::default()
+        };
+        let args = flags.merge(document);
+        assert_eq!(args.name, Some("core".to_string()));
+        assert_eq!(args.ip, Some("10.0.0.3".to_string()));
+    }
 }
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// Seconds ssh may take to connect before giving up
+const SSH_CONNECT_TIMEOUT: u64 = 10;
+
+pub fn default_command() -> String {
+    "sudo shutdown -h now".to_string()
+}
+
+// How disable puts a Wake-on-LAN device to sleep or shuts it down
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+pub enum Shutdown {
+    // Runs a command on the device's host with ssh
+    Ssh {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<String>,
+        // Private key to log in with, ssh's own keys and agent are used otherwise
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<PathBuf>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+        #[serde(default = "default_command")]
+        command: String,
+    },
+    // A magic packet with the MAC address reversed, which sleep-on-lan listens for. It is
+    // sent like the device's magic packets, to another UDP port when one is set.
+    SleepOnLan {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+    },
+}
+
+impl Shutdown {
+    pub fn method(&self) -> &'static str {
+        match self {
+            Shutdown::Ssh { .. } => "ssh",
+            Shutdown::SleepOnLan { .. } => "sleep-on-lan",
+        }
+    }
+
+    // The command and where it runs, or where the packet goes
+    pub fn describe(&self, host: Option<&str>, destination: SocketAddr) -> String {
+        match self {
+            Shutdown::Ssh {
+                user,
+                port,
+                command,
+                ..
+            } => {
+                let mut target = host.unwrap_or("no host set").to_string();
+                if let Some(user) = user {
+                    target = format!("{}@{}", user, target);
+                }
+                if let Some(port) = port {
+                    target = format!("{} port {}", target, port);
+                }
+                format!("ssh {} \"{}\"", target, command)
+            }
+            Shutdown::SleepOnLan { port } => format!(
+                "sleep-on-LAN packet to {}",
+                SocketAddr::new(destination.ip(), port.unwrap_or(destination.port()))
+            ),
+        }
+    }
+}
+
+// The arguments to ssh for running the command on the host. Batch mode makes ssh fail
+// instead of asking for a password or to accept an unknown host key.
+pub fn ssh_args(
+    host: &str,
+    user: Option<&str>,
+    key: Option<&PathBuf>,
+    port: Option<u16>,
+    command: &str,
+) -> Vec<String> {
+    let mut args = vec![
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-o".to_string(),
+        format!("ConnectTimeout={}", SSH_CONNECT_TIMEOUT),
+    ];
+
+    if let Some(key) = key {
+        args.extend(["-i".to_string(), key.display().to_string()]);
+    }
+    if let Some(port) = port {
+        args.extend(["-p".to_string(), port.to_string()]);
+    }
+
+    let destination = match user {
+        Some(user) => format!("{}@{}", user, host),
+        None => host.to_string(),
+    };
+    args.extend([destination, "--".to_string(), command.to_string()]);
+
+    args
+}
+
+// Runs ssh and waits for it to exit. A host that shuts down while the command runs drops
+// the connection, which ssh reports as an error even though the command worked.
+pub async fn run_ssh(host: &str, args: Vec<String>) -> Result<(), String> {
+    run(Command::new("ssh"), host, args).await
+}
+
+async fn run(mut ssh: Command, host: &str, args: Vec<String>) -> Result<(), String> {
+    let dropped = format!("Connection to {} closed by remote host", host);
+    let output = tokio::task::spawn_blocking(move || ssh.args(&args).stdin(Stdio::null()).output())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Cannot run ssh: {}", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() || stderr.contains(&dropped) {
+        return Ok(());
+    }
+
+    match stderr.trim() {
+        "" => Err(format!("ssh failed with {}", output.status)),
+        stderr => Err(format!("ssh failed with {}: {}", output.status, stderr)),
+    }
+}
+
+// The MAC address sleep-on-lan expects, the device's own with its octets reversed
+pub fn sleep_on_lan_mac(mac: &[u8; 6]) -> [u8; 6] {
+    let mut reversed = *mac;
+    reversed.reverse();
+    reversed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssh_args() {
+        let key = PathBuf::from("/home/admin/.ssh/id_ed25519");
+        assert_eq!(
+            ssh_args("10.0.20.5", Some("admin"), Some(&key), Some(2222), "poweroff").join(" "),
+            "-o BatchMode=yes -o ConnectTimeout=10 -i /home/admin/.ssh/id_ed25519 -p 2222 admin@10.0.20.5 -- poweroff"
+        );
+        assert_eq!(
+            ssh_args("nas", None, None, None, "poweroff").join(" "),
+            "-o BatchMode=yes -o ConnectTimeout=10 nas -- poweroff"
+        );
+    }
+
+    #[test]
+    fn test_shutdown_from_toml() {
+        let shutdown: Shutdown = toml::from_str("method = \"ssh\"\nuser = \"admin\"").unwrap();
+        assert_eq!(
+            shutdown,
+            Shutdown::Ssh {
+                user: Some("admin".to_string()),
+                key: None,
+                port: None,
+                command: default_command(),
+            }
+        );
+        let destination: SocketAddr = "255.255.255.255:9".parse().unwrap();
+        assert_eq!(
+            shutdown.describe(Some("nas"), destination),
+            "ssh admin@nas \"sudo shutdown -h now\""
+        );
+
+        let shutdown: Shutdown = toml::from_str("method = \"sleep-on-lan\"").unwrap();
+        assert_eq!(shutdown, Shutdown::SleepOnLan { port: None });
+        assert_eq!(
+            Shutdown::SleepOnLan { port: Some(7) }.describe(None, destination),
+            "sleep-on-LAN packet to 255.255.255.255:7"
+        );
+    }
+
+    #[test]
+    fn test_sleep_on_lan_mac() {
+        assert_eq!(
+            sleep_on_lan_mac(&[0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c]),
+            [0x0c, 0xbb, 0xaa, 0x21, 0x1b, 0x00]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_with_fake_ssh() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // An ssh on PATH that writes down its arguments, one per line, and fails like
+        // OpenSSH for the commands named fail and drop
+        let dir = std::env::temp_dir().join(format!("rackcli-ssh-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = dir.join("ssh");
+        std::fs::write(
+            &fake,
+            "#!/bin/sh
+printf '%s\\n' \"$@\" > \"$0.args\"
+case \"$*\" in
+*fail) echo 'Permission denied (publickey).' >&2; exit 255 ;;
+*drop) echo 'Connection to nas closed by remote host.' >&2; exit 255 ;;
+esac
+",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let run_fake = |command: &str| {
+            let mut ssh = Command::new("ssh");
+            ssh.env("PATH", &dir);
+            run(
+                ssh,
+                "nas",
+                ssh_args("nas", Some("admin"), None, Some(2222), command),
+            )
+        };
+        let result = run_fake("sudo poweroff").await;
+        let written = std::fs::read_to_string(dir.join("ssh.args"));
+        let failed = run_fake("fail").await;
+        let dropped = run_fake("drop").await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            written.unwrap().lines().collect::<Vec<_>>(),
+            [
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "ConnectTimeout=10",
+                "-p",
+                "2222",
+                "admin@nas",
+                "--",
+                "sudo poweroff"
+            ]
+        );
+        assert_eq!(
+            failed,
+            Err("ssh failed with exit status: 255: Permission denied (publickey).".to_string())
+        );
+        assert_eq!(dropped, Ok(()));
+    }
+}
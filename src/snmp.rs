@@ -1,150 +1,1231 @@
 use std::time::Duration;
 
 use crate::errors::SnmpError;
+use crate::interface::{Interface, IF_ALIAS, IF_HIGH_SPEED, IF_NAME, IF_OPER_STATUS};
 use crate::snmpv2::SnmpV2Client;
 use crate::snmpv3::SnmpV3Client;
-use crate::switch::{SNMPVersion, Switch, SwitchResult};
-use snmp2::Oid;
-use tokio::task::JoinSet;
+use crate::switch::{PseStatus, SNMPVersion, Switch, SwitchPort, SwitchResult};
+use crate::switch_oid::SwitchOid;
+use crate::utils::{get_detection_status, get_power_class, get_pse_status};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use snmp2::{Oid, Pdu, Value};
+
+// Most agents answer a request of this many varbinds in a single UDP datagram
+const MAX_VARBINDS: usize = 64;
+
+// Error status returned by the agent when the response does not fit in one message
+const ERROR_STATUS_TOO_BIG: u32 = 1;
+
+const DEFAULT_PORT: u16 = 161;
+const DEFAULT_TIMEOUT: u64 = 5;
+const DEFAULT_RETRIES: u32 = 0;
+// Retries double the timeout each time, so more than this would wait for hours
+const MAX_RETRIES: u32 = 10;
+
+// POWER-ETHERNET-MIB telemetry read by status, port columns are indexed by group and port
+// and main PSE columns by group
+const PSE_PORT_ENTRY: [u64; 10] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1];
+const PSE_PORT_DETECTION_STATUS: u64 = 6;
+const PSE_PORT_POWER_CLASSIFICATIONS: u64 = 10;
+const MAIN_PSE_ENTRY: [u64; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 3, 1, 1];
+const MAIN_PSE_POWER: u64 = 2;
+const MAIN_PSE_OPER_STATUS: u64 = 3;
+const MAIN_PSE_CONSUMPTION_POWER: u64 = 4;
+
+// Repetitions asked for in each GETBULK request of a walk
+const WALK_REPETITIONS: u32 = 32;
 
 pub trait SnmpClient {
-    async fn get(self, oid: Oid, port: u64) -> Result<SwitchResult, SnmpError>;
-    async fn set(self, oid: Oid, value: i64, port: u64) -> Result<SwitchResult, SnmpError>;
+    async fn get(&mut self, oids: &[Oid<'static>]) -> Result<Vec<SnmpValue>, SnmpError>;
+    async fn set(&mut self, oids: &[Oid<'static>], value: i64)
+        -> Result<Vec<SnmpValue>, SnmpError>;
+    async fn get_bulk(
+        &mut self,
+        oid: &Oid<'static>,
+        repetitions: u32,
+    ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError>;
+}
+
+// The session for a switch, v2c or v3 depending on its settings
+enum SwitchClient {
+    V2(SnmpV2Client),
+    V3(SnmpV3Client),
+}
+
+impl SnmpClient for SwitchClient {
+    async fn get(&mut self, oids: &[Oid<'static>]) -> Result<Vec<SnmpValue>, SnmpError> {
+        match self {
+            SwitchClient::V2(client) => client.get(oids).await,
+            SwitchClient::V3(client) => client.get(oids).await,
+        }
+    }
+
+    async fn set(
+        &mut self,
+        oids: &[Oid<'static>],
+        value: i64,
+    ) -> Result<Vec<SnmpValue>, SnmpError> {
+        match self {
+            SwitchClient::V2(client) => client.set(oids, value).await,
+            SwitchClient::V3(client) => client.set(oids, value).await,
+        }
+    }
+
+    async fn get_bulk(
+        &mut self,
+        oid: &Oid<'static>,
+        repetitions: u32,
+    ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+        match self {
+            SwitchClient::V2(client) => client.get_bulk(oid, repetitions).await,
+            SwitchClient::V3(client) => client.get_bulk(oid, repetitions).await,
+        }
+    }
+}
+
+// A value read from the agent, copied out of the response buffer
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SnmpValue {
+    Integer(i64),
+    Unsigned(u64),
+    OctetString(Vec<u8>),
+    ObjectIdentifier(Vec<u64>),
+    EndOfMibView,
+    Other,
+}
+
+impl From<Value<'_>> for SnmpValue {
+    fn from(value: Value<'_>) -> Self {
+        match value {
+            Value::Integer(value) => SnmpValue::Integer(value),
+            Value::Unsigned32(value) | Value::Counter32(value) => SnmpValue::Unsigned(value.into()),
+            Value::Counter64(value) => SnmpValue::Unsigned(value),
+            Value::OctetString(bytes) => SnmpValue::OctetString(bytes.to_vec()),
+            Value::ObjectIdentifier(oid) => oid
+                .iter()
+                .map(|arcs| SnmpValue::ObjectIdentifier(arcs.collect()))
+                .unwrap_or(SnmpValue::Other),
+            Value::EndOfMibView => SnmpValue::EndOfMibView,
+            _ => SnmpValue::Other,
+        }
+    }
+}
+
+impl std::fmt::Display for SnmpValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnmpValue::Integer(value) => write!(f, "{}", value),
+            SnmpValue::Unsigned(value) => write!(f, "{}", value),
+            SnmpValue::OctetString(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            SnmpValue::ObjectIdentifier(arcs) => write!(f, "{}", format_oid(arcs)),
+            SnmpValue::EndOfMibView => write!(f, "endOfMibView"),
+            SnmpValue::Other => write!(f, "Unknown"),
+        }
+    }
+}
+
+pub(crate) fn format_oid(arcs: &[u64]) -> String {
+    arcs.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<String>>()
+        .join(".")
 }
 
 pub struct Snmp {}
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operation {
+    Get,
+    Set(i64),
+}
+
+// SNMP transport settings, set per switch or globally in the [snmp] section of config.toml.
+// Unset switch values fall back to the global ones and then to the built-in defaults.
+#[derive(Args, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnmpSettings {
+    /// UDP port of the SNMP agent [default: 161]
+    #[clap(long = "snmp-port", value_name = "PORT", env = "RACKCLI_SNMP_PORT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Seconds to wait for a reply [default: 5]
+    #[clap(long, value_name = "SECONDS", env = "RACKCLI_TIMEOUT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Times to resend an unanswered request, doubling the timeout each time [default: 0]
+    #[clap(long, env = "RACKCLI_RETRIES")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl SnmpSettings {
+    // Values set here win over the given defaults
+    pub fn or(self, defaults: SnmpSettings) -> SnmpSettings {
+        SnmpSettings {
+            port: self.port.or(defaults.port),
+            timeout: self.timeout.or(defaults.timeout),
+            retries: self.retries.or(defaults.retries),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == SnmpSettings::default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == Some(0) {
+            return Err("SNMP port must be between 1 and 65535".to_string());
+        }
+        if self.timeout == Some(0) {
+            return Err("Timeout must be at least 1 second".to_string());
+        }
+        if self.retries.is_some_and(|retries| retries > MAX_RETRIES) {
+            return Err(format!("Retries must be at most {}", MAX_RETRIES));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn get_port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_PORT)
+    }
+
+    pub(crate) fn get_retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT)),
+            retries: self.retries.unwrap_or(DEFAULT_RETRIES),
+        }
+    }
+}
+
+impl std::fmt::Display for SnmpSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut settings = Vec::new();
+        if let Some(port) = self.port {
+            settings.push(format!("port {}", port));
+        }
+        if let Some(timeout) = self.timeout {
+            settings.push(format!("timeout {}s", timeout));
+        }
+        if let Some(retries) = self.retries {
+            settings.push(format!("retries {}", retries));
+        }
+
+        write!(f, "{}", settings.join(", "))
+    }
+}
+
+// AsyncSession waits forever for a reply, so every request is bounded by the policy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct RetryPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl RetryPolicy {
+    // Each retry waits twice as long as the attempt before it
+    pub(crate) fn timeout_for(&self, attempt: u32) -> Duration {
+        self.timeout
+            .saturating_mul(2u32.saturating_pow(attempt.min(MAX_RETRIES)))
+    }
+
+    // Time spent waiting before giving up
+    pub(crate) fn total(&self) -> Duration {
+        (0..=self.retries)
+            .map(|attempt| self.timeout_for(attempt))
+            .sum()
+    }
+}
+
+// A request as sent to the agent, kept whole so that it can be resent
+pub(crate) enum Request<'a> {
+    Get(Vec<&'a Oid<'a>>),
+    Set(Vec<(&'a Oid<'a>, Value<'a>)>),
+    GetBulk(&'a Oid<'a>, u32),
+}
+
+impl<'a> Request<'a> {
+    pub(crate) fn get(oids: &'a [Oid<'a>]) -> Self {
+        Request::Get(oids.iter().collect())
+    }
+
+    pub(crate) fn set(oids: &'a [Oid<'a>], value: i64) -> Self {
+        Request::Set(
+            oids.iter()
+                .map(|oid| (oid, Value::Integer(value)))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Request::Get(oids) => oids.len(),
+            Request::Set(values) => values.len(),
+            Request::GetBulk(..) => 1,
+        }
+    }
+}
+
+fn check_error_status(pdu: &Pdu) -> Result<(), SnmpError> {
+    match pdu.error_status {
+        0 => Ok(()),
+        ERROR_STATUS_TOO_BIG => Err(SnmpError::TooBig),
+        status => Err(SnmpError::AgentError(format!(
+            "{} for varbind {}",
+            error_status_name(status),
+            pdu.error_index
+        ))),
+    }
+}
+
+// Reads one value per requested varbind, in request order
+pub(crate) fn read_values(pdu: Pdu, expected: usize) -> Result<Vec<SnmpValue>, SnmpError> {
+    check_error_status(&pdu)?;
+
+    let values: Vec<SnmpValue> = pdu.varbinds.map(|(_, value)| value.into()).collect();
+
+    if values.is_empty() {
+        return Err(SnmpError::OperationError(
+            "No value found in response".to_string(),
+        ));
+    }
+
+    if values.len() != expected {
+        return Err(SnmpError::OperationError(format!(
+            "Expected {} values in response, got {}",
+            expected,
+            values.len()
+        )));
+    }
+
+    Ok(values)
+}
+
+// Reads the OID and value of every varbind in a GETBULK response
+pub(crate) fn read_varbinds(pdu: Pdu) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+    check_error_status(&pdu)?;
+
+    pdu.varbinds
+        .map(|(oid, value)| {
+            let oid = oid
+                .iter()
+                .map(|arcs| arcs.collect())
+                .ok_or_else(|| SnmpError::OperationError(format!("Invalid OID {}", oid)))?;
+
+            Ok((oid, SnmpValue::from(value)))
+        })
+        .collect()
+}
+
+// Names from RFC 3416
+fn error_status_name(status: u32) -> String {
+    match status {
+        1 => "tooBig",
+        2 => "noSuchName",
+        3 => "badValue",
+        4 => "readOnly",
+        5 => "genErr",
+        6 => "noAccess",
+        7 => "wrongType",
+        8 => "wrongLength",
+        9 => "wrongEncoding",
+        10 => "wrongValue",
+        11 => "noCreation",
+        12 => "inconsistentValue",
+        13 => "resourceUnavailable",
+        14 => "commitFailed",
+        15 => "undoFailed",
+        16 => "authorizationError",
+        17 => "notWritable",
+        18 => "inconsistentName",
+        _ => return format!("error status {}", status),
+    }
+    .to_string()
+}
+
 impl Snmp {
     pub fn new() -> Self {
         Self {}
     }
 
-    pub async fn get(&self, switch: &Switch) -> Result<Vec<SwitchResult>, SnmpError> {
-        let mut req_set = JoinSet::new();
+    // Reads the admin state of the ports, then the PoE telemetry of the ports that answered
+    // and the power budget of their PSEs
+    pub async fn get(
+        &self,
+        switch: &Switch,
+        ports: &[SwitchPort],
+    ) -> Result<(Vec<SwitchResult>, Vec<PseStatus>), SnmpError> {
+        let profile = switch.get_profile().map_err(SnmpError::OperationError)?;
+        let mut client = Snmp::connect(switch).await?;
+
+        let mut results =
+            Snmp::run_ports(&mut client, &switch.name, &profile, ports, Operation::Get).await;
+
+        // Nothing answered, so asking for telemetry would only wait for more timeouts
+        if results.iter().all(|result| result.error.is_some()) {
+            return Ok((results, vec![]));
+        }
+
+        let default_group = switch.get_pse_group();
+        Snmp::read_port_telemetry(
+            &mut client,
+            default_group,
+            switch.get_port_power_oid(),
+            &mut results,
+        )
+        .await;
+
+        let mut groups: Vec<u64> = results
+            .iter()
+            .filter(|result| result.error.is_none())
+            .map(|result| result.group.unwrap_or(default_group))
+            .collect();
+        groups.sort();
+        groups.dedup();
+
+        let mut pse = Vec::new();
+        for group in groups {
+            pse.extend(Snmp::read_pse_status(&mut client, group).await);
+        }
+
+        Ok((results, pse))
+    }
+
+    pub async fn set(
+        &self,
+        switch: &Switch,
+        value: i64,
+        ports: &[SwitchPort],
+    ) -> Result<Vec<SwitchResult>, SnmpError> {
+        self.run(switch, ports, Operation::Set(value)).await
+    }
+
+    // Reads the given objects, ex: sysDescr.0
+    pub async fn get_values(
+        &self,
+        switch: &Switch,
+        oids: &[&[u64]],
+    ) -> Result<Vec<SnmpValue>, SnmpError> {
+        let oids: Vec<Oid<'static>> = oids
+            .iter()
+            .map(|oid| Oid::from(oid).expect("Invalid OID"))
+            .collect();
+
+        let mut client = Snmp::connect(switch).await?;
+        client.get(&oids).await
+    }
+
+    // Reads ifName, ifAlias, ifOperStatus and ifHighSpeed of each interface
+    pub async fn get_interfaces(
+        &self,
+        switch: &Switch,
+        if_indexes: &[u64],
+    ) -> Result<Vec<Interface>, SnmpError> {
+        let mut client = Snmp::connect(switch).await?;
+
+        Snmp::read_interfaces(&mut client, if_indexes).await
+    }
+
+    // Reads every object below the base OID, returning the index after the base with its value
+    pub async fn walk(
+        &self,
+        switch: &Switch,
+        base_oid: &[u64],
+    ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+        let mut client = Snmp::connect(switch).await?;
+
+        Snmp::walk_client(&mut client, base_oid).await
+    }
+
+    // One session per switch, so v3 engine discovery only happens once
+    async fn run(
+        &self,
+        switch: &Switch,
+        ports: &[SwitchPort],
+        operation: Operation,
+    ) -> Result<Vec<SwitchResult>, SnmpError> {
+        let profile = switch.get_profile().map_err(SnmpError::OperationError)?;
+        let mut client = Snmp::connect(switch).await?;
+
+        let results = Snmp::run_ports(&mut client, &switch.name, &profile, ports, operation).await;
+
+        Ok(results)
+    }
+
+    async fn connect(switch: &Switch) -> Result<SwitchClient, SnmpError> {
+        let socket_addr = switch.resolve_socket_addr().await?;
+        let retry = switch.get_retry_policy();
 
         match switch.get_version() {
             SNMPVersion::V2 => {
-                let mut switch_results = Vec::new();
-                for port in switch.get_ports().iter() {
-                    let oid = Snmp::make_oid(switch.get_oid(), *port);
-                    let v2 = SnmpV2Client::new(
-                        switch.get_socket_addr(),
-                        switch.get_community().as_bytes(),
-                        Some(Duration::from_secs(5)),
-                    )?;
-
-                    req_set.spawn(v2.get(oid, *port));
-                }
+                let v2 = SnmpV2Client::new(socket_addr, switch.get_community().as_bytes(), retry)
+                    .await?;
 
-                // Print the results
-                while let Some(result_result) = req_set.join_next().await {
-                    match result_result {
-                        Ok(Ok(switch_result)) => switch_results.push(switch_result),
-                        Ok(Err(e)) => println!("Error: {}", e),
-                        Err(e) => println!("Task error: {}", e),
-                    }
-                }
-
-                Ok(switch_results)
+                Ok(SwitchClient::V2(v2))
             }
             SNMPVersion::V3 => {
-                let mut switch_results = Vec::new();
                 let auth_password = switch.get_or_prompt_auth_password();
                 let privacy_password = switch.get_or_prompt_privacy_password();
-                for port in switch.get_ports().iter() {
-                    let oid = Snmp::make_oid(switch.get_oid(), *port);
-                    let v3 = SnmpV3Client::new(
-                        switch.get_socket_addr(),
-                        switch.get_username(),
-                        &auth_password,
-                        switch.get_auth_protocol(),
-                        switch.get_privacy_protocol(),
-                        &privacy_password,
-                        Some(Duration::from_secs(5)),
-                    )?;
-
-                    req_set.spawn(v3.get(oid, *port));
+                let v3 = SnmpV3Client::new(
+                    socket_addr,
+                    switch.get_username(),
+                    &auth_password,
+                    switch.get_auth_protocol(),
+                    switch.get_privacy_protocol(),
+                    &privacy_password,
+                    retry,
+                )
+                .await?;
+
+                Ok(SwitchClient::V3(v3))
+            }
+        }
+    }
+
+    // Walks with GETBULK until the agent returns an OID outside the base, asking for fewer
+    // repetitions when the agent says the response would not fit
+    async fn walk_client(
+        client: &mut impl SnmpClient,
+        base_oid: &[u64],
+    ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+        let mut entries = Vec::new();
+        let mut last = base_oid.to_vec();
+        let mut repetitions = WALK_REPETITIONS;
+
+        loop {
+            let oid = Oid::from(last.as_slice()).expect("Invalid OID");
+            let varbinds = match client.get_bulk(&oid, repetitions).await {
+                Ok(varbinds) => varbinds,
+                Err(SnmpError::TooBig) if repetitions > 1 => {
+                    repetitions /= 2;
+                    continue;
                 }
+                Err(e) => return Err(e),
+            };
 
-                // Print the results
-                while let Some(result_result) = req_set.join_next().await {
-                    match result_result {
-                        Ok(Ok(switch_result)) => switch_results.push(switch_result),
-                        Ok(Err(e)) => println!("Error: {}", e),
-                        Err(e) => println!("Task error: {}", e),
-                    }
+            if varbinds.is_empty() {
+                return Ok(entries);
+            }
+
+            for (oid, value) in varbinds {
+                if value == SnmpValue::EndOfMibView || !oid.starts_with(base_oid) {
+                    return Ok(entries);
                 }
 
-                Ok(switch_results)
+                // An agent that does not move forward would keep the walk going forever
+                if oid <= last {
+                    return Err(SnmpError::OperationError(format!(
+                        "Agent returned {} out of order",
+                        format_oid(&oid)
+                    )));
+                }
+
+                entries.push((oid[base_oid.len()..].to_vec(), value));
+                last = oid;
             }
         }
     }
 
-    pub async fn set(&self, switch: &Switch, value: i64) -> Result<Vec<SwitchResult>, SnmpError> {
-        let mut req_set = JoinSet::new();
-
-        match switch.get_version() {
-            SNMPVersion::V2 => {
-                let mut switch_results = Vec::new();
-                for port in switch.get_ports().iter() {
-                    let oid = Snmp::make_oid(switch.get_oid(), *port);
-                    let v2 = SnmpV2Client::new(
-                        switch.get_socket_addr(),
-                        switch.get_community().as_bytes(),
-                        Some(Duration::from_secs(5)),
-                    )?;
-
-                    req_set.spawn(v2.set(oid, value, *port));
-                }
+    // Sends the ports as multi-varbind requests, falling back to one request per port when
+    // the agent rejects a batch, so one bad port does not fail the others. The brand's
+    // profile gives the OID of each port and the name of each admin state. Once a request
+    // times out the switch is taken to be unreachable and the remaining ports are not sent.
+    async fn run_ports(
+        client: &mut impl SnmpClient,
+        switch_name: &str,
+        profile: &SwitchOid,
+        ports: &[SwitchPort],
+        operation: Operation,
+    ) -> Vec<SwitchResult> {
+        let mut switch_results = Vec::new();
+        let mut timed_out: Option<String> = None;
 
-                // Print the results
-                while let Some(result_result) = req_set.join_next().await {
-                    match result_result {
-                        Ok(Ok(switch_result)) => switch_results.push(switch_result),
-                        Ok(Err(e)) => println!("Error: {}", e),
-                        Err(e) => println!("Task error: {}", e),
+        for batch in ports.chunks(MAX_VARBINDS) {
+            let mut requested = Vec::new();
+            let mut oids: Vec<Oid<'static>> = Vec::new();
+            for port in batch {
+                match Snmp::port_oid(profile, port) {
+                    Ok(oid) => {
+                        requested.push(*port);
+                        oids.push(oid);
                     }
+                    Err(e) => switch_results.push(SwitchResult::error(switch_name, *port, e)),
                 }
+            }
 
-                Ok(switch_results)
+            if let Some(e) = &timed_out {
+                for port in requested {
+                    switch_results.push(SwitchResult::error(switch_name, port, e));
+                }
+                continue;
             }
-            SNMPVersion::V3 => {
-                let mut switch_results = Vec::new();
-                let auth_password = switch.get_or_prompt_auth_password();
-                let privacy_password = switch.get_or_prompt_privacy_password();
-                for port in switch.get_ports().iter() {
-                    let oid = Snmp::make_oid(switch.get_oid(), *port);
-                    let v3 = SnmpV3Client::new(
-                        switch.get_socket_addr(),
-                        switch.get_username(),
-                        &auth_password,
-                        switch.get_auth_protocol(),
-                        switch.get_privacy_protocol(),
-                        &privacy_password,
-                        Some(Duration::from_secs(5)),
-                    )?;
-
-                    req_set.spawn(v3.set(oid, value, *port));
+            if oids.is_empty() {
+                continue;
+            }
+
+            match Snmp::request(client, &oids, operation).await {
+                Ok(values) => {
+                    for (port, value) in requested.iter().zip(values) {
+                        let status = profile.get_status(&value);
+                        switch_results.push(SwitchResult::new(switch_name, *port, status));
+                    }
                 }
+                Err(SnmpError::TooBig | SnmpError::AgentError(_)) if oids.len() > 1 => {
+                    for (port, oid) in requested.iter().zip(oids) {
+                        if let Some(e) = &timed_out {
+                            switch_results.push(SwitchResult::error(switch_name, *port, e));
+                            continue;
+                        }
 
-                // Print the results
-                while let Some(result_result) = req_set.join_next().await {
-                    match result_result {
-                        Ok(Ok(switch_result)) => switch_results.push(switch_result),
-                        Ok(Err(e)) => println!("Error: {}", e),
-                        Err(e) => println!("Task error: {}", e),
+                        let result = Snmp::request(client, &[oid], operation).await;
+                        switch_results.push(match result {
+                            Ok(values) => {
+                                let status = profile.get_status(&values[0]);
+                                SwitchResult::new(switch_name, *port, status)
+                            }
+                            Err(e) => {
+                                if let SnmpError::Timeout(_) = e {
+                                    timed_out = Some(e.to_string());
+                                }
+                                SwitchResult::error(switch_name, *port, &e)
+                            }
+                        });
+                    }
+                }
+                Err(e) => {
+                    if let SnmpError::Timeout(_) = e {
+                        timed_out = Some(e.to_string());
+                    }
+                    for port in requested {
+                        switch_results.push(SwitchResult::error(switch_name, port, &e));
                     }
                 }
+            }
+        }
+
+        switch_results
+    }
+
+    fn port_oid(profile: &SwitchOid, port: &SwitchPort) -> Result<Oid<'static>, String> {
+        let oid = profile.get_port_oid(port.group, port.port)?;
+
+        Oid::from(oid.as_slice()).map_err(|_| format!("Invalid OID {}", format_oid(&oid)))
+    }
+
+    // Telemetry is optional, so an agent without it or a failed request leaves it unset.
+    // The vendor power column is indexed like pethPsePortTable and read in milliwatts.
+    // Ports without a stack member are on the default group.
+    async fn read_port_telemetry(
+        client: &mut impl SnmpClient,
+        default_group: u64,
+        port_power_oid: Option<Vec<u64>>,
+        results: &mut [SwitchResult],
+    ) {
+        let mut columns = vec![
+            [&PSE_PORT_ENTRY[..], &[PSE_PORT_DETECTION_STATUS]].concat(),
+            [&PSE_PORT_ENTRY[..], &[PSE_PORT_POWER_CLASSIFICATIONS]].concat(),
+        ];
+        columns.extend(port_power_oid);
+
+        let mut answered: Vec<&mut SwitchResult> = results
+            .iter_mut()
+            .filter(|result| result.error.is_none())
+            .collect();
+
+        for batch in answered.chunks_mut(MAX_VARBINDS / columns.len()) {
+            let oids: Vec<Oid<'static>> = batch
+                .iter()
+                .flat_map(|result| {
+                    let index = [result.group.unwrap_or(default_group), result.port];
+                    columns
+                        .iter()
+                        .map(move |column| Snmp::make_oid(column, &index))
+                })
+                .collect();
+
+            let values = match client.get(&oids).await {
+                Ok(values) => values,
+                Err(_) => continue,
+            };
+
+            for (result, values) in batch.iter_mut().zip(values.chunks(columns.len())) {
+                if let SnmpValue::Integer(_) = values[0] {
+                    result.detection = Some(get_detection_status(&values[0]));
+                }
+                if let SnmpValue::Integer(_) = values[1] {
+                    result.power_class = Some(get_power_class(&values[1]));
+                }
+                result.power_mw = match values.get(2) {
+                    Some(SnmpValue::Unsigned(milliwatts)) => Some(*milliwatts),
+                    Some(SnmpValue::Integer(milliwatts)) => u64::try_from(*milliwatts).ok(),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    async fn read_pse_status(client: &mut impl SnmpClient, group: u64) -> Option<PseStatus> {
+        let oids = [
+            MAIN_PSE_POWER,
+            MAIN_PSE_OPER_STATUS,
+            MAIN_PSE_CONSUMPTION_POWER,
+        ]
+        .map(|column| Snmp::make_oid(&[&MAIN_PSE_ENTRY[..], &[column]].concat(), &[group]));
+
+        let values = client.get(&oids).await.ok()?;
+        if values.iter().all(|value| *value == SnmpValue::Other) {
+            return None;
+        }
+
+        // Both power columns are Gauge32 watts
+        let watts = |value: &SnmpValue| match value {
+            SnmpValue::Unsigned(watts) => Some(*watts),
+            _ => None,
+        };
+
+        Some(PseStatus {
+            group,
+            status: get_pse_status(&values[1]),
+            power: watts(&values[0]),
+            consumption: watts(&values[2]),
+        })
+    }
+
+    async fn read_interfaces(
+        client: &mut impl SnmpClient,
+        if_indexes: &[u64],
+    ) -> Result<Vec<Interface>, SnmpError> {
+        let columns = [&IF_NAME[..], &IF_ALIAS, &IF_OPER_STATUS, &IF_HIGH_SPEED];
+        let mut interfaces = Vec::new();
+
+        for batch in if_indexes.chunks(MAX_VARBINDS / columns.len()) {
+            let oids: Vec<Oid<'static>> = batch
+                .iter()
+                .flat_map(|if_index| {
+                    columns
+                        .iter()
+                        .map(move |column| Snmp::make_oid(column, &[*if_index]))
+                })
+                .collect();
+
+            let values = client.get(&oids).await?;
+            interfaces.extend(values.chunks(columns.len()).map(Interface::from_values));
+        }
+
+        Ok(interfaces)
+    }
+
+    async fn request(
+        client: &mut impl SnmpClient,
+        oids: &[Oid<'static>],
+        operation: Operation,
+    ) -> Result<Vec<SnmpValue>, SnmpError> {
+        match operation {
+            Operation::Get => client.get(oids).await,
+            Operation::Set(value) => client.set(oids, value).await,
+        }
+    }
+
+    // Appends the row index to a column, ex: group and port for pethPsePortTable
+    fn make_oid(column: &[u64], index: &[u64]) -> Oid<'static> {
+        let oid = [column, index].concat();
+
+        Oid::from(oid.as_slice()).expect("Invalid OID")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::switch_oid::{BrandProfile, SwitchOidBuilder};
+    use std::collections::BTreeMap;
+
+    // Answers batches of up to max_varbinds, rejects port 4 and times out on port 9 on SET
+    // and walks the table
+    struct FakeClient {
+        max_varbinds: usize,
+        requests: usize,
+        table: Vec<(Vec<u64>, SnmpValue)>,
+    }
+
+    impl SnmpClient for FakeClient {
+        async fn get(&mut self, oids: &[Oid<'static>]) -> Result<Vec<SnmpValue>, SnmpError> {
+            self.requests += 1;
+            if oids.len() > self.max_varbinds {
+                return Err(SnmpError::TooBig);
+            }
+
+            Ok(oids
+                .iter()
+                .map(|oid| {
+                    let oid: Vec<u64> = oid.iter().unwrap().collect();
+                    self.table
+                        .iter()
+                        .find(|(entry, _)| *entry == oid)
+                        .map(|(_, value)| value.clone())
+                        .unwrap_or(SnmpValue::Other)
+                })
+                .collect())
+        }
+
+        async fn set(
+            &mut self,
+            oids: &[Oid<'static>],
+            value: i64,
+        ) -> Result<Vec<SnmpValue>, SnmpError> {
+            self.requests += 1;
+            if oids.iter().any(|oid| oid.to_string().ends_with(".4")) {
+                return Err(SnmpError::AgentError(
+                    "wrongValue for varbind 4".to_string(),
+                ));
+            }
+            if oids.iter().any(|oid| oid.to_string().ends_with(".9")) {
+                return Err(SnmpError::Timeout(Duration::from_secs(5)));
+            }
+
+            Ok(oids.iter().map(|_| SnmpValue::Integer(value)).collect())
+        }
+
+        async fn get_bulk(
+            &mut self,
+            oid: &Oid<'static>,
+            repetitions: u32,
+        ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+            self.requests += 1;
+            if repetitions as usize > self.max_varbinds {
+                return Err(SnmpError::TooBig);
+            }
+
+            let start: Vec<u64> = oid.iter().unwrap().collect();
+            let mut varbinds: Vec<(Vec<u64>, SnmpValue)> = self
+                .table
+                .iter()
+                .filter(|(oid, _)| *oid > start)
+                .take(repetitions as usize)
+                .cloned()
+                .collect();
+
+            if varbinds.len() < repetitions as usize {
+                varbinds.push((start, SnmpValue::EndOfMibView));
+            }
+
+            Ok(varbinds)
+        }
+    }
+
+    // Admin state column 1.3.1, indexed by port as in fake_table
+    fn test_profile(port_offset: i64) -> SwitchOid {
+        SwitchOid::from(&BrandProfile {
+            name: "Test".to_string(),
+            oid: "1.3.1".to_string(),
+            on: 1,
+            off: 2,
+            status: BTreeMap::from([(3, "Fault".to_string())]),
+            port_offset,
+            group_index: false,
+            enterprises: vec![],
+        })
+    }
+
+    fn fake_table() -> Vec<(Vec<u64>, SnmpValue)> {
+        let mut table: Vec<(Vec<u64>, SnmpValue)> = (1..=40)
+            .map(|port| (vec![1, 3, 1, port], SnmpValue::Integer(1)))
+            .collect();
+        table.push((vec![1, 4, 1, 1], SnmpValue::Integer(3)));
+        table
+    }
+
+    #[test]
+    fn test_make_oid() {
+        let oid = Snmp::make_oid(&[1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 3], &[1, 7]);
+        assert_eq!(oid.to_string(), "1.3.6.1.2.1.105.1.1.1.3.1.7");
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let retry = RetryPolicy {
+            timeout: Duration::from_secs(2),
+            retries: 2,
+        };
 
-                Ok(switch_results)
+        assert_eq!(retry.timeout_for(0), Duration::from_secs(2));
+        assert_eq!(retry.timeout_for(2), Duration::from_secs(8));
+        assert_eq!(retry.total(), Duration::from_secs(14));
+    }
+
+    #[test]
+    fn test_snmp_settings_fall_back_to_defaults() {
+        let switch = SnmpSettings {
+            timeout: Some(2),
+            ..Default::default()
+        };
+        let global = SnmpSettings {
+            port: Some(1161),
+            timeout: Some(10),
+            retries: None,
+        };
+
+        let settings = switch.or(global);
+        assert_eq!(settings.get_port(), 1161);
+        assert_eq!(
+            settings.get_retry_policy(),
+            RetryPolicy {
+                timeout: Duration::from_secs(2),
+                retries: 0,
             }
+        );
+        assert_eq!(SnmpSettings::default().get_port(), 161);
+    }
+
+    #[test]
+    fn test_snmp_settings_validate() {
+        assert!(SnmpSettings::default().validate().is_ok());
+        assert!(SnmpSettings {
+            timeout: Some(0),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(SnmpSettings {
+            retries: Some(11),
+            ..Default::default()
         }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_ports_single_request() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: fake_table(),
+        };
+        let ports: Vec<SwitchPort> = (1..=40).map(SwitchPort::from).collect();
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            &test_profile(0),
+            &ports,
+            Operation::Get,
+        )
+        .await;
+
+        assert_eq!(client.requests, 1);
+        assert_eq!(results.len(), 40);
+        assert!(results.iter().all(|result| result.status == "On"));
     }
 
-    fn make_oid(oid_vec: Vec<u64>, port: u64) -> Oid<'static> {
-        let mut new_vec = oid_vec.to_vec();
-        new_vec.push(port);
+    #[tokio::test]
+    async fn test_run_ports_stack_member() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![(
+                vec![1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 3, 2, 14],
+                SnmpValue::Integer(1),
+            )],
+        };
+        let builder = SwitchOidBuilder::with_profiles(&[]);
+        let port = SwitchPort {
+            group: Some(2),
+            port: 14,
+        };
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            builder.get_profile("Cisco").unwrap(),
+            &[port],
+            Operation::Get,
+        )
+        .await;
+
+        assert_eq!(results[0].status, "On");
+        assert_eq!(results[0].get_port(), port);
+    }
+
+    #[tokio::test]
+    async fn test_run_ports_profile_offset_and_status() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![
+                (vec![1, 3, 1, 101], SnmpValue::Integer(1)),
+                (vec![1, 3, 1, 102], SnmpValue::Integer(3)),
+            ],
+        };
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            &test_profile(100),
+            &[1, 2, 3].map(SwitchPort::from),
+            Operation::Get,
+        )
+        .await;
+
+        assert_eq!(results[0].status, "On");
+        assert_eq!(results[1].status, "Fault");
+        assert_eq!(results[2].status, "Unknown");
+    }
+
+    #[tokio::test]
+    async fn test_run_ports_falls_back_on_too_big() {
+        let mut client = FakeClient {
+            max_varbinds: 1,
+            requests: 0,
+            table: fake_table(),
+        };
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            &test_profile(0),
+            &[1, 2, 3].map(SwitchPort::from),
+            Operation::Get,
+        )
+        .await;
+
+        assert_eq!(client.requests, 4);
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.port)
+                .collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+        assert!(results.iter().all(|result| result.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_run_ports_set_isolates_rejected_port() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![],
+        };
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            &test_profile(0),
+            &[3, 4, 5].map(SwitchPort::from),
+            Operation::Set(2),
+        )
+        .await;
+
+        assert_eq!(results[0].status, "Off");
+        assert!(results[1].error.is_some());
+        assert_eq!(results[2].status, "Off");
+    }
+
+    #[tokio::test]
+    async fn test_run_ports_stops_after_timeout() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![],
+        };
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            &test_profile(0),
+            &[3, 4, 9, 10].map(SwitchPort::from),
+            Operation::Set(2),
+        )
+        .await;
+
+        // The batch, then ports 3, 4 and 9 on their own, port 10 is not sent
+        assert_eq!(client.requests, 4);
+        assert_eq!(results[0].status, "Off");
+        assert!(results[1].error.is_some());
+        assert_eq!(
+            results[3].error,
+            Some("SNMP request timed out after 5s".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_ports_invalid_port() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: fake_table(),
+        };
+
+        let results = Snmp::run_ports(
+            &mut client,
+            "core",
+            &test_profile(-2),
+            &[1, 3].map(SwitchPort::from),
+            Operation::Get,
+        )
+        .await;
+
+        assert_eq!(client.requests, 1);
+        assert_eq!(
+            results[0].error,
+            Some("Port 1 has no index, check the port offset of brand Test".to_string())
+        );
+        assert_eq!(results[1].status, "On");
+    }
+
+    #[tokio::test]
+    async fn test_walk_stops_at_end_of_base() {
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: fake_table(),
+        };
+
+        let entries = Snmp::walk_client(&mut client, &[1, 3]).await.unwrap();
+
+        assert_eq!(client.requests, 2);
+        assert_eq!(entries.len(), 40);
+        assert_eq!(entries[0], (vec![1, 1], SnmpValue::Integer(1)));
+    }
+
+    #[tokio::test]
+    async fn test_walk_asks_for_fewer_on_too_big() {
+        let mut client = FakeClient {
+            max_varbinds: 10,
+            requests: 0,
+            table: fake_table(),
+        };
+
+        let entries = Snmp::walk_client(&mut client, &[1, 3]).await.unwrap();
+
+        // 32 and 16 are too big, then 8 at a time for 40 rows and the end of the base
+        assert_eq!(client.requests, 8);
+        assert_eq!(entries.len(), 40);
+    }
+
+    #[tokio::test]
+    async fn test_read_port_telemetry() {
+        let port_oid = |column: u64, port: u64| [&PSE_PORT_ENTRY[..], &[column, 1, port]].concat();
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![
+                (port_oid(6, 1), SnmpValue::Integer(3)),
+                (port_oid(10, 1), SnmpValue::Integer(3)),
+                (port_oid(6, 2), SnmpValue::Integer(2)),
+            ],
+        };
+        let mut results = vec![
+            SwitchResult::new("core", 1, "On".to_string()),
+            SwitchResult::new("core", 2, "On".to_string()),
+            SwitchResult::error("core", 3, "timeout"),
+        ];
+
+        Snmp::read_port_telemetry(&mut client, 1, None, &mut results).await;
+
+        assert_eq!(client.requests, 1);
+        assert_eq!(results[0].detection, Some("Delivering power".to_string()));
+        assert_eq!(results[0].power_class, Some("Class 2".to_string()));
+        assert_eq!(results[1].detection, Some("Searching".to_string()));
+        assert_eq!(results[1].power_class, None);
+        assert_eq!(results[2].detection, None);
+    }
+
+    #[tokio::test]
+    async fn test_read_port_telemetry_vendor_power() {
+        let power_oid = vec![1, 3, 6, 1, 4, 1, 9, 9, 402, 1, 2, 1, 9];
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![(
+                [&power_oid[..], &[1, 2]].concat(),
+                SnmpValue::Unsigned(4200),
+            )],
+        };
+        let mut results = vec![
+            SwitchResult::new("core", 1, "On".to_string()),
+            SwitchResult::new("core", 2, "On".to_string()),
+        ];
+
+        Snmp::read_port_telemetry(&mut client, 1, Some(power_oid), &mut results).await;
+
+        assert_eq!(results[0].power_mw, None);
+        assert_eq!(results[1].power_mw, Some(4200));
+    }
+
+    #[tokio::test]
+    async fn test_read_pse_status() {
+        let pse_oid = |column: u64| [&MAIN_PSE_ENTRY[..], &[column, 2]].concat();
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: vec![
+                (pse_oid(2), SnmpValue::Unsigned(370)),
+                (pse_oid(3), SnmpValue::Integer(1)),
+                (pse_oid(4), SnmpValue::Unsigned(45)),
+            ],
+        };
+
+        assert_eq!(
+            Snmp::read_pse_status(&mut client, 2).await,
+            Some(PseStatus {
+                group: 2,
+                status: "On".to_string(),
+                power: Some(370),
+                consumption: Some(45),
+            })
+        );
+        assert_eq!(Snmp::read_pse_status(&mut client, 1).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_read_interfaces() {
+        let column = |column: &[u64], if_index: u64| [column, &[if_index]].concat();
+        let mut client = FakeClient {
+            max_varbinds: 64,
+            requests: 0,
+            table: (1..=20)
+                .flat_map(|if_index| {
+                    [
+                        (
+                            column(&IF_NAME, if_index),
+                            SnmpValue::OctetString(format!("g{}", if_index).into_bytes()),
+                        ),
+                        (column(&IF_OPER_STATUS, if_index), SnmpValue::Integer(2)),
+                    ]
+                })
+                .collect(),
+        };
+        let if_indexes: Vec<u64> = (1..=20).collect();
+
+        let interfaces = Snmp::read_interfaces(&mut client, &if_indexes)
+            .await
+            .unwrap();
+
+        assert_eq!(client.requests, 2);
+        assert_eq!(interfaces.len(), 20);
+        assert_eq!(interfaces[19].name, Some("g20".to_string()));
+        assert_eq!(interfaces[19].oper_status, Some("Down".to_string()));
+        assert_eq!(interfaces[19].speed_mbps, None);
+    }
+
+    #[test]
+    fn test_snmp_value_display() {
+        assert_eq!(SnmpValue::Integer(3).to_string(), "3");
+        assert_eq!(
+            SnmpValue::OctetString(b"Cisco IOS".to_vec()).to_string(),
+            "Cisco IOS"
+        );
+        assert_eq!(
+            SnmpValue::ObjectIdentifier(vec![1, 3, 6, 1, 4, 1, 9]).to_string(),
+            "1.3.6.1.4.1.9"
+        );
+    }
 
-        Oid::from(new_vec.as_slice()).expect("Invalid OID")
+    #[test]
+    fn test_error_status_name() {
+        assert_eq!(error_status_name(17), "notWritable");
+        assert_eq!(error_status_name(99), "error status 99");
     }
 }
@@ -1,63 +1,147 @@
-use crate::utils::get_status;
-use crate::{errors::SnmpError, snmp::SnmpClient, switch::SwitchResult};
-use snmp2::{Oid, SyncSession, Value};
-use std::{net::SocketAddr, time::Duration};
+use crate::snmp::{read_values, read_varbinds, Request, RetryPolicy, SnmpValue};
+use crate::{errors::SnmpError, snmp::SnmpClient};
+use snmp2::{AsyncSession, Oid, Pdu};
+use std::net::SocketAddr;
 
 pub struct SnmpV2Client {
-    session: SyncSession,
+    session: AsyncSession,
+    retry: RetryPolicy,
+    socket_addr: SocketAddr,
+    community: Vec<u8>,
+    // A request timed out, so its reply may still arrive on the session's socket
+    stale: bool,
 }
 
 impl SnmpV2Client {
-    pub fn new(
+    pub async fn new(
         socket_addr: SocketAddr,
         community: &[u8],
-        timeout: Option<Duration>,
+        retry: RetryPolicy,
     ) -> Result<Self, SnmpError> {
-        match SyncSession::new_v2c(socket_addr, community, timeout, 0) {
-            Ok(session) => Ok(Self { session }),
-            Err(e) => Err(SnmpError::SessionError(e.to_string())),
-        }
+        Ok(Self {
+            session: Self::connect(socket_addr, community).await?,
+            retry,
+            socket_addr,
+            community: community.to_vec(),
+            stale: false,
+        })
     }
-}
 
-impl SnmpClient for SnmpV2Client {
-    async fn get(mut self, oid: Oid<'_>, port: u64) -> Result<SwitchResult, SnmpError> {
-        let response = self.session.get(&oid);
+    async fn connect(socket_addr: SocketAddr, community: &[u8]) -> Result<AsyncSession, SnmpError> {
+        AsyncSession::new_v2c(socket_addr, community, 0)
+            .await
+            .map_err(|e| SnmpError::SessionError(e.to_string()))
+    }
+
+    // Sends the request until it is answered or the retries run out, then reads the response
+    async fn send<T>(
+        &mut self,
+        request: Request<'_>,
+        read: fn(Pdu, usize) -> Result<T, SnmpError>,
+    ) -> Result<T, SnmpError> {
+        let mut attempt = 0;
 
-        match response {
-            Ok(mut pdu) => {
-                if let Some((_, value)) = pdu.varbinds.next() {
-                    let status = get_status(value);
+        loop {
+            // snmp2 reuses the request ID after a timeout and reads the first reply on the
+            // socket, so a late reply would be taken as the answer to this request. A new
+            // session has its own socket, which late replies do not reach.
+            if self.stale {
+                self.session = Self::connect(self.socket_addr, &self.community).await?;
+                self.stale = false;
+            }
 
-                    return Ok(SwitchResult { port, status });
+            let timeout = self.retry.timeout_for(attempt);
+            let response = match &request {
+                Request::Get(oids) => {
+                    tokio::time::timeout(timeout, self.session.get_many(oids)).await
                 }
+                Request::Set(values) => {
+                    tokio::time::timeout(timeout, self.session.set(values)).await
+                }
+                Request::GetBulk(oid, repetitions) => {
+                    tokio::time::timeout(timeout, self.session.getbulk(&[*oid], 0, *repetitions))
+                        .await
+                }
+            };
 
-                Err(SnmpError::OperationError(
-                    "No value found in response".to_string(),
-                ))
+            match response {
+                Ok(Ok(pdu)) => return read(pdu, request.len()),
+                Ok(Err(e)) => return Err(SnmpError::OperationError(e.to_string())),
+                Err(_) => {
+                    self.stale = true;
+                    if attempt == self.retry.retries {
+                        return Err(SnmpError::Timeout(self.retry.total()));
+                    }
+                    attempt += 1;
+                }
             }
-            Err(e) => Err(SnmpError::OperationError(e.to_string())),
         }
     }
+}
+
+impl SnmpClient for SnmpV2Client {
+    async fn get(&mut self, oids: &[Oid<'static>]) -> Result<Vec<SnmpValue>, SnmpError> {
+        self.send(Request::get(oids), read_values).await
+    }
 
-    async fn set(mut self, oid: Oid<'_>, value: i64, port: u64) -> Result<SwitchResult, SnmpError> {
-        let port_value = Value::Integer(value);
-        let response = self.session.set(&[(&oid, port_value)]);
+    async fn set(
+        &mut self,
+        oids: &[Oid<'static>],
+        value: i64,
+    ) -> Result<Vec<SnmpValue>, SnmpError> {
+        self.send(Request::set(oids, value), read_values).await
+    }
 
-        match response {
-            Ok(mut pdu) => {
-                if let Some((_, value)) = pdu.varbinds.next() {
-                    let status = get_status(value);
+    async fn get_bulk(
+        &mut self,
+        oid: &Oid<'static>,
+        repetitions: u32,
+    ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+        self.send(Request::GetBulk(oid, repetitions), |pdu, _| {
+            read_varbinds(pdu)
+        })
+        .await
+    }
+}
 
-                    return Ok(SwitchResult { port, status });
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
 
-                Err(SnmpError::OperationError(
-                    "No value found in response".to_string(),
-                ))
-            }
+    // Answers a v2c GET with community "public" by sending it back as a response, which
+    // only changes the PDU type that follows the version and community
+    async fn answer(agent: &UdpSocket) -> SocketAddr {
+        let mut buffer = [0u8; 1500];
+        let (length, from) = agent.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(buffer[13], 0xa0);
+        buffer[13] = 0xa2;
+        agent.send_to(&buffer[..length], from).await.unwrap();
+        from
+    }
 
-            Err(e) => Err(SnmpError::OperationError(e.to_string())),
-        }
+    #[tokio::test]
+    async fn test_new_session_after_timeout() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let retry = RetryPolicy {
+            timeout: Duration::from_millis(100),
+            retries: 0,
+        };
+        let mut client = SnmpV2Client::new(agent.local_addr().unwrap(), b"public", retry)
+            .await
+            .unwrap();
+        let oids = [Oid::from(&[1, 3, 6, 1, 2, 1, 1, 1, 0]).unwrap()];
+
+        // The first request is only answered after it timed out
+        assert!(matches!(
+            client.get(&oids).await,
+            Err(SnmpError::Timeout(_))
+        ));
+        let first = answer(&agent).await;
+
+        let (result, second) = tokio::join!(client.get(&oids), answer(&agent));
+        assert!(result.is_ok());
+        assert_ne!(first, second);
     }
 }
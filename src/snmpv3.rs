@@ -1,23 +1,28 @@
+use crate::snmp::{read_values, read_varbinds, Request, RetryPolicy, SnmpValue};
 use crate::switch::{SNMPAuth, SNMPEncryption};
-use crate::utils::get_status;
-use crate::{errors::SnmpError, snmp::SnmpClient, switch::SwitchResult};
+use crate::{errors::SnmpError, snmp::SnmpClient};
 use snmp2::v3::{Auth::AuthPriv, AuthProtocol, Cipher, Security};
-use snmp2::{Oid, SyncSession, Value};
-use std::{net::SocketAddr, time::Duration};
+use snmp2::{AsyncSession, Oid, Pdu};
+use std::net::SocketAddr;
 
 pub struct SnmpV3Client {
-    session: SyncSession,
+    session: AsyncSession,
+    retry: RetryPolicy,
+    socket_addr: SocketAddr,
+    security: Security,
+    // A request timed out, so its reply may still arrive on the session's socket
+    stale: bool,
 }
 
 impl SnmpV3Client {
-    pub fn new(
+    pub async fn new(
         socket_addr: SocketAddr,
         username: &[u8],
         password: &[u8],
         auth_protocol: SNMPAuth,
         encryption: SNMPEncryption,
         encryption_key: &[u8],
-        timeout: Option<Duration>,
+        retry: RetryPolicy,
     ) -> Result<Self, SnmpError> {
         if password.is_empty() {
             return Err(SnmpError::SessionError(
@@ -34,83 +39,128 @@ impl SnmpV3Client {
         };
 
         let enc = match encryption {
-            SNMPEncryption::Des => Cipher::Des,
-            SNMPEncryption::Aes128 => Cipher::Aes128,
-            SNMPEncryption::Aes192 => Cipher::Aes192,
-            SNMPEncryption::Aes256 => Cipher::Aes256,
-            _ => unreachable!(),
+            SNMPEncryption::None => None,
+            SNMPEncryption::Des => Some(Cipher::Des),
+            SNMPEncryption::Aes128 => Some(Cipher::Aes128),
+            SNMPEncryption::Aes192 => Some(Cipher::Aes192),
+            SNMPEncryption::Aes256 => Some(Cipher::Aes256),
         };
 
-        let security = if encryption == SNMPEncryption::None {
-            Security::new(username, password).with_auth_protocol(auth)
-        } else {
-            Security::new(username, password)
+        let security = match enc {
+            None => Security::new(username, password).with_auth_protocol(auth),
+            Some(cipher) => Security::new(username, password)
                 .with_auth_protocol(auth)
                 .with_auth(AuthPriv {
-                    cipher: enc,
+                    cipher,
                     privacy_password: encryption_key.to_vec(),
-                })
+                }),
         };
 
-        match SyncSession::new_v3(socket_addr, timeout, 0, security) {
-            Ok(mut session) => {
-                // First attempt to discover the engine ID
-                match session.init() {
-                    Ok(_) => Ok(Self { session }),
-                    Err(e) => Err(SnmpError::SessionError(format!(
+        Ok(Self {
+            session: Self::connect(socket_addr, &security, retry).await?,
+            retry,
+            socket_addr,
+            security,
+            stale: false,
+        })
+    }
+
+    async fn connect(
+        socket_addr: SocketAddr,
+        security: &Security,
+        retry: RetryPolicy,
+    ) -> Result<AsyncSession, SnmpError> {
+        let mut session = AsyncSession::new_v3(socket_addr, 0, security.clone())
+            .await
+            .map_err(|e| SnmpError::SessionError(format!("Session creation failed: {}", e)))?;
+
+        // First attempt to discover the engine ID
+        let mut attempt = 0;
+        loop {
+            let result = tokio::time::timeout(retry.timeout_for(attempt), session.init()).await;
+
+            match result {
+                Ok(Ok(_)) => return Ok(session),
+                Ok(Err(e)) => {
+                    return Err(SnmpError::SessionError(format!(
                         "Session init failed: {}",
                         e
-                    ))),
+                    )))
                 }
+                Err(_) if attempt < retry.retries => attempt += 1,
+                Err(_) => return Err(SnmpError::Timeout(retry.total())),
             }
-            Err(e) => Err(SnmpError::SessionError(format!(
-                "Session creation failed: {}",
-                e
-            ))),
         }
     }
-}
 
-impl SnmpClient for SnmpV3Client {
-    async fn get(mut self, oid: Oid<'_>, port: u64) -> Result<SwitchResult, SnmpError> {
-        let response = self.session.get(&oid);
+    // Sends the request until it is answered or the retries run out, then reads the response
+    async fn send<T>(
+        &mut self,
+        request: Request<'_>,
+        read: fn(Pdu, usize) -> Result<T, SnmpError>,
+    ) -> Result<T, SnmpError> {
+        let mut attempt = 0;
+        let mut auth_updated = false;
 
-        let response = match response {
-            Err(snmp2::Error::AuthUpdated) => {
-                // Authentication keys have been updated, retry the GET request
-                self.session.get(&oid)
+        loop {
+            // A new session after a timeout, for the same reason as SnmpV2Client::send
+            if self.stale {
+                self.session = Self::connect(self.socket_addr, &self.security, self.retry).await?;
+                self.stale = false;
             }
-            other => other,
-        };
 
-        match response {
-            Ok(mut pdu) => match pdu.varbinds.next() {
-                Some((_, value)) => Ok(SwitchResult { port, status: get_status(value) }),
-                None => Err(SnmpError::OperationError("No value found in response".to_string())),
-            },
-            Err(e) => Err(SnmpError::OperationError(e.to_string())),
+            let timeout = self.retry.timeout_for(attempt);
+            let response = match &request {
+                Request::Get(oids) => {
+                    tokio::time::timeout(timeout, self.session.get_many(oids)).await
+                }
+                Request::Set(values) => {
+                    tokio::time::timeout(timeout, self.session.set(values)).await
+                }
+                Request::GetBulk(oid, repetitions) => {
+                    tokio::time::timeout(timeout, self.session.getbulk(&[*oid], 0, *repetitions))
+                        .await
+                }
+            };
+
+            match response {
+                // Authentication keys have been updated, resend the request once
+                Ok(Err(snmp2::Error::AuthUpdated)) if !auth_updated => auth_updated = true,
+                Ok(Ok(pdu)) => return read(pdu, request.len()),
+                Ok(Err(e)) => return Err(SnmpError::OperationError(e.to_string())),
+                Err(_) => {
+                    self.stale = true;
+                    if attempt == self.retry.retries {
+                        return Err(SnmpError::Timeout(self.retry.total()));
+                    }
+                    attempt += 1;
+                }
+            }
         }
     }
+}
 
-    async fn set(mut self, oid: Oid<'_>, value: i64, port: u64) -> Result<SwitchResult, SnmpError> {
-        let port_value = Value::Integer(value);
-        let response = self.session.set(&[(&oid, port_value)]);
+impl SnmpClient for SnmpV3Client {
+    async fn get(&mut self, oids: &[Oid<'static>]) -> Result<Vec<SnmpValue>, SnmpError> {
+        self.send(Request::get(oids), read_values).await
+    }
 
-        let response = match response {
-            Err(snmp2::Error::AuthUpdated) => {
-                // Authentication keys have been updated, retry the SET request
-                let retry_value = Value::Integer(value);
-                self.session.set(&[(&oid, retry_value)])
-            }
-            other => other,
-        };
+    async fn set(
+        &mut self,
+        oids: &[Oid<'static>],
+        value: i64,
+    ) -> Result<Vec<SnmpValue>, SnmpError> {
+        self.send(Request::set(oids, value), read_values).await
+    }
 
-        match response {
-            Ok(mut pdu) => match pdu.varbinds.next() {
-                Some((_, value)) => Ok(SwitchResult { port, status: get_status(value) }),
-                None => Err(SnmpError::OperationError("No value found in response".to_string())),
-            },
-            Err(e) => Err(SnmpError::OperationError(e.to_string())),
-        }
+    async fn get_bulk(
+        &mut self,
+        oid: &Oid<'static>,
+        repetitions: u32,
+    ) -> Result<Vec<(Vec<u64>, SnmpValue)>, SnmpError> {
+        self.send(Request::GetBulk(oid, repetitions), |pdu, _| {
+            read_varbinds(pdu)
+        })
+        .await
     }
 }
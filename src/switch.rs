@@ -1,11 +1,29 @@
+use crate::bridge::{
+    self, ForwardingTable, MacLocation, DOT1D_BASE_PORT_IF_INDEX, DOT1D_TP_FDB_PORT,
+    DOT1Q_TP_FDB_PORT, DOT1Q_VLAN_FDB_ID,
+};
+use crate::errors::SnmpError;
+use crate::interface::{self, IF_NAME, IF_TYPE};
+use crate::lldp::{
+    self, LLDP_LOC_PORT_DESC, LLDP_LOC_PORT_ID, LLDP_REM_MAN_ADDR_IF_SUBTYPE, LLDP_REM_PORT_ID,
+    LLDP_REM_PORT_ID_SUBTYPE, LLDP_REM_SYS_NAME,
+};
+use crate::mac::MacAddress;
+use crate::output::{self, OutputFormat};
+use crate::rackcliargs::SwitchArgs;
 use crate::{device::Device, keyring};
+use clap::ValueEnum;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 
-use crate::snmp::Snmp;
-use crate::switch_oid::SwitchOidBuilder;
+use crate::snmp::{RetryPolicy, Snmp, SnmpSettings, SnmpValue};
+use crate::switch_oid::{self, SwitchOid, SwitchOidBuilder};
+use crate::utils::is_interactive;
 
-use std::net::{IpAddr, SocketAddr};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Switch {
@@ -14,6 +32,9 @@ pub struct Switch {
     brand: String,
     version: SNMPVersion,
     ports: u64,
+    // Members of a stacked switch, each with its own PSE group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stack_members: Option<u64>,
     keyring: bool,
     #[serde(skip)]
     community: String,
@@ -24,22 +45,228 @@ pub struct Switch {
     encryption: SNMPEncryption,
     #[serde(skip)]
     encryption_pass: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<SwitchPort, String>,
+    // PoE port indexes found by discover, keyed by PSE group
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    poe_ports: BTreeMap<u64, Vec<u64>>,
+    // ifIndex of each port, matched by interface name and keyed by PSE group and port
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    if_indexes: BTreeMap<u64, BTreeMap<u64, u64>>,
+    #[serde(default, skip_serializing_if = "SnmpSettings::is_empty")]
+    snmp: SnmpSettings,
+    #[serde(skip)]
+    snmp_defaults: SnmpSettings,
+    #[serde(skip)]
+    selected_ports: Option<String>,
+    #[serde(skip)]
+    output: OutputFormat,
+    #[serde(skip)]
+    pse: Vec<PseStatus>,
+    #[serde(skip)]
+    show_interfaces: bool,
+    #[serde(skip)]
+    show_neighbors: bool,
+    #[serde(skip)]
+    given_secrets: SwitchSecrets,
 }
 
-#[derive(Clone)]
+#[derive(Serialize, Clone)]
 pub struct SwitchResult {
+    pub switch: String,
+    pub group: Option<u64>,
+    pub port: u64,
+    pub label: Option<String>,
+    pub status: String,
+    pub detection: Option<String>,
+    pub power_class: Option<String>,
+    pub power_mw: Option<u64>,
+    pub interface: Option<String>,
+    pub description: Option<String>,
+    pub link: Option<String>,
+    pub speed_mbps: Option<u64>,
+    pub neighbor: Option<String>,
+    pub neighbor_port: Option<String>,
+    pub neighbor_address: Option<String>,
+    pub pse_status: Option<String>,
+    pub pse_power_w: Option<u64>,
+    pub pse_consumption_w: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl SwitchResult {
+    pub fn new(switch: &str, port: impl Into<SwitchPort>, status: String) -> Self {
+        let port = port.into();
+        Self {
+            switch: switch.to_string(),
+            group: port.group,
+            port: port.port,
+            label: None,
+            status,
+            detection: None,
+            power_class: None,
+            power_mw: None,
+            interface: None,
+            description: None,
+            link: None,
+            speed_mbps: None,
+            neighbor: None,
+            neighbor_port: None,
+            neighbor_address: None,
+            pse_status: None,
+            pse_power_w: None,
+            pse_consumption_w: None,
+            error: None,
+        }
+    }
+
+    pub fn error(switch: &str, port: impl Into<SwitchPort>, error: impl std::fmt::Display) -> Self {
+        let port = port.into();
+        Self {
+            switch: switch.to_string(),
+            group: port.group,
+            port: port.port,
+            label: None,
+            status: STATUS_ERROR.to_string(),
+            detection: None,
+            power_class: None,
+            power_mw: None,
+            interface: None,
+            description: None,
+            link: None,
+            speed_mbps: None,
+            neighbor: None,
+            neighbor_port: None,
+            neighbor_address: None,
+            pse_status: None,
+            pse_power_w: None,
+            pse_consumption_w: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub(crate) fn get_port(&self) -> SwitchPort {
+        SwitchPort {
+            group: self.group,
+            port: self.port,
+        }
+    }
+}
+
+// A port, with the stack member it is on for stacked switches, ex: 2/14
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SwitchPort {
+    pub group: Option<u64>,
     pub port: u64,
+}
+
+impl From<u64> for SwitchPort {
+    fn from(port: u64) -> Self {
+        Self { group: None, port }
+    }
+}
+
+impl FromStr for SwitchPort {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Switch::parse_port(input.trim()).ok_or_else(|| format!("Invalid port: {}", input))
+    }
+}
+
+// Written as in a port list, so that labels are keyed by 14 or 2/14 in config.toml
+impl Serialize for SwitchPort {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SwitchPort {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+// State and power budget of a power sourcing equipment group, in watts
+#[derive(Clone, Debug, PartialEq)]
+pub struct PseStatus {
+    pub group: u64,
     pub status: String,
+    pub power: Option<u64>,
+    pub consumption: Option<u64>,
+}
+
+// A port label as given on the command line, ex: 3=lobby-camera or 2/5=ap-2
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortLabel {
+    pub port: SwitchPort,
+    pub label: String,
+}
+
+impl FromStr for PortLabel {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (port, label) = input
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid port label: {}, expected PORT=LABEL", input))?;
+
+        let port = port
+            .parse::<SwitchPort>()
+            .map_err(|_| format!("Invalid port label: {}, expected PORT=LABEL", input))?;
+
+        Ok(Self {
+            port,
+            label: label.trim().to_string(),
+        })
+    }
+}
+
+enum Host {
+    Ip(IpAddr),
+    Name(String),
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+// Switch settings as shown by list, without any secrets
+#[derive(Serialize)]
+pub struct SwitchRecord {
+    pub name: String,
+    pub ip: String,
+    pub brand: String,
+    pub ports: u64,
+    pub stack_members: Option<u64>,
+    pub keyring: bool,
+    pub version: String,
+    pub username: Option<String>,
+    pub auth: Option<String>,
+    pub encryption: Option<String>,
+    pub labels: Option<String>,
+    pub poe_ports: Option<String>,
+    pub snmp_port: Option<u16>,
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+}
+
+// What the agent reports about itself, as shown by identify
+#[derive(Serialize)]
+pub struct SwitchIdentity {
+    pub switch: String,
+    pub sys_object_id: String,
+    pub sys_descr: String,
+    pub detected_brand: Option<String>,
+    pub brand: String,
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, PartialEq, Clone, Copy, Debug, Default)]
 pub enum SNMPVersion {
     V2,
     #[default]
     V3,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+#[derive(Serialize, Deserialize, ValueEnum, PartialEq, Clone, Copy, Debug, Default)]
 pub enum SNMPAuth {
     #[default]
     Md5,
@@ -50,7 +277,7 @@ pub enum SNMPAuth {
     Sha512,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+#[derive(Serialize, Deserialize, ValueEnum, PartialEq, Clone, Copy, Debug, Default)]
 pub enum SNMPEncryption {
     #[default]
     None,
@@ -60,7 +287,18 @@ pub enum SNMPEncryption {
     Aes256,
 }
 
+// SNMPv2-MIB system group
+const SYS_DESCR: [u64; 9] = [1, 3, 6, 1, 2, 1, 1, 1, 0];
+const SYS_OBJECT_ID: [u64; 9] = [1, 3, 6, 1, 2, 1, 1, 2, 0];
+
+// pethPsePortAdminEnable, indexed by PSE group and port
+const PSE_PORT_ADMIN_ENABLE: [u64; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 3];
+
 const STATUS_ON: &str = "On";
+const DETECTION_DELIVERING: &str = "Delivering power";
+const DETECTION_FAULT: &str = "Fault";
+const DETECTION_OTHER_FAULT: &str = "Other fault";
+const STATUS_ERROR: &str = "Error";
 
 struct Credentials {
     username: String,
@@ -71,10 +309,27 @@ struct Credentials {
     encryption_pass: String,
 }
 
+// Secrets given with a flag or a RACKCLI_* variable, which are used instead of prompting
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwitchSecrets {
+    pub community: Option<String>,
+    pub auth_pass: Option<String>,
+    pub encryption_pass: Option<String>,
+}
+
+fn prompt_password(prompt: &str) -> String {
+    dialoguer::Password::new()
+        .with_prompt(prompt)
+        .with_confirmation("Confirm Password", "Passwords do not match")
+        .interact()
+        .unwrap()
+}
+
 fn collect_credentials(
     version: SNMPVersion,
     keyring: bool,
     current: Option<&Switch>,
+    given: &SwitchSecrets,
 ) -> Credentials {
     let mut username = current.map(|s| s.auth_user.clone()).unwrap_or_default();
     let mut password = String::new();
@@ -85,11 +340,14 @@ fn collect_credentials(
 
     if version == SNMPVersion::V2 {
         if keyring {
-            community = dialoguer::Input::<String>::new()
-                .with_prompt("Community")
-                .default(community)
-                .interact()
-                .unwrap();
+            community = match &given.community {
+                Some(given) => given.clone(),
+                None => dialoguer::Input::<String>::new()
+                    .with_prompt("Community")
+                    .default(community)
+                    .interact()
+                    .unwrap(),
+            };
         }
     } else {
         auth = match dialoguer::Select::new()
@@ -120,11 +378,10 @@ fn collect_credentials(
             .unwrap();
 
         if keyring {
-            password = dialoguer::Password::new()
-                .with_prompt("Password")
-                .with_confirmation("Confirm Password", "Passwords do not match")
-                .interact()
-                .unwrap();
+            password = given
+                .auth_pass
+                .clone()
+                .unwrap_or_else(|| prompt_password("Password"));
         }
 
         encryption = match dialoguer::Select::new()
@@ -147,11 +404,10 @@ fn collect_credentials(
         };
 
         if encryption != SNMPEncryption::None && keyring {
-            encryption_pass = dialoguer::Password::new()
-                .with_prompt("Encryption Password")
-                .with_confirmation("Confirm Password", "Passwords do not match")
-                .interact()
-                .unwrap();
+            encryption_pass = given
+                .encryption_pass
+                .clone()
+                .unwrap_or_else(|| prompt_password("Encryption Password"));
         }
     }
 
@@ -165,62 +421,36 @@ fn collect_credentials(
     }
 }
 
+fn runtime_secret(var: &str, prompt: impl FnOnce() -> String) -> Result<String, String> {
+    match std::env::var(var) {
+        Ok(value) => Ok(value),
+        Err(_) if is_interactive() => Ok(prompt()),
+        Err(_) => Err(format!(
+            "{} is not set and there is no terminal to prompt for it",
+            var
+        )),
+    }
+}
+
 impl Device for Switch {
     async fn disable(&mut self) -> std::io::Result<()> {
-        let off = SwitchOidBuilder::new()
-            .get_off(&self.brand)
-            .expect("Invalid brand");
+        let results = self.power_off().await?;
+        self.report(&results);
 
-        self.set(off).await
+        self.check_powered_off(&results)
     }
 
     async fn enable(&mut self) -> std::io::Result<()> {
-        let on = SwitchOidBuilder::new()
-            .get_on(&self.brand)
-            .expect("Invalid brand");
+        let results = self.power_on().await?;
+        self.report(&results);
 
-        self.set(on).await
+        Ok(())
     }
 
     async fn status(&mut self) {
-        if !self.keyring {
-            match self.version {
-                SNMPVersion::V2 => {
-                    self.community = dialoguer::Input::<String>::new()
-                        .with_prompt("Community")
-                        .default(self.community.clone())
-                        .interact()
-                        .unwrap();
-                }
-                SNMPVersion::V3 => {
-                    self.auth_pass = dialoguer::Password::new()
-                        .with_prompt("Auth Password")
-                        .interact()
-                        .unwrap();
-
-                    if self.encryption != SNMPEncryption::None {
-                        self.encryption_pass = dialoguer::Password::new()
-                            .with_prompt("Encryption Password")
-                            .interact()
-                            .unwrap();
-                    }
-                }
-            }
-        }
-
-        let client = Snmp::new();
-        let results = client.get(&self.clone()).await;
-
-        match results {
-            Ok(results) => {
-                println!("Status for {}:", self.name);
-                for result in results {
-                    println!("\t{}", result);
-                }
-            }
-            Err(e) => {
-                println!("Error getting status for {}: {}", self.name, e);
-            }
+        match self.port_status().await {
+            Ok(results) => self.report(&results),
+            Err(e) => output::print_notice(self.output, e),
         }
     }
 
@@ -228,8 +458,9 @@ impl Device for Switch {
         let sob = SwitchOidBuilder::new();
 
         let ip = dialoguer::Input::<String>::new()
-            .with_prompt("IP")
+            .with_prompt("IP or hostname")
             .default(self.ip.clone())
+            .validate_with(|input: &String| Switch::parse_host(input).map(|_| ()))
             .interact()
             .unwrap();
 
@@ -239,6 +470,29 @@ impl Device for Switch {
             .interact()
             .unwrap();
 
+        let stack_members = dialoguer::Input::<u64>::new()
+            .with_prompt("Stack members")
+            .default(self.stack_members.unwrap_or(1))
+            .validate_with(|input: &u64| Switch::validate_stack_members(Some(*input)))
+            .interact()
+            .unwrap();
+
+        // The default is not validated, so it keeps the current labels
+        let mut labels = self.labels.clone();
+        dialoguer::Input::<String>::new()
+            .with_prompt("Port labels (ex: 3=lobby-camera,2/5=ap-2)")
+            .default(self.get_labels())
+            .allow_empty(true)
+            .validate_with(|input: &String| -> Result<(), String> {
+                let parsed = Switch::parse_labels(input)?;
+                Switch::validate_labels(&parsed)?;
+                labels = parsed;
+                Ok(())
+            })
+            .interact()
+            .unwrap();
+
+        // A brand whose profile was removed or rejected is no longer offered
         let brand = sob.get_oid_name(
             dialoguer::Select::new()
                 .with_prompt("Brand")
@@ -246,7 +500,7 @@ impl Device for Switch {
                     sob.get_oid_names()
                         .iter()
                         .position(|x| x == &self.brand)
-                        .unwrap(),
+                        .unwrap_or(0),
                 )
                 .items(sob.get_oid_names().as_slice())
                 .interact()
@@ -275,7 +529,7 @@ impl Device for Switch {
             .unwrap()
             == 0;
 
-        let credentials = collect_credentials(version, keyring, Some(self));
+        let credentials = collect_credentials(version, keyring, Some(self), &self.given_secrets);
 
         if self.keyring && !keyring {
             self.remove_keys();
@@ -283,6 +537,8 @@ impl Device for Switch {
 
         self.ip = ip;
         self.ports = ports;
+        self.stack_members = (stack_members > 1).then_some(stack_members);
+        self.labels = labels;
         self.brand = brand;
         self.community = credentials.community;
         self.auth = credentials.auth;
@@ -295,7 +551,7 @@ impl Device for Switch {
 }
 
 impl Switch {
-    pub fn create(switch_names: Vec<String>) -> Self {
+    pub async fn create(switch_names: Vec<String>, secrets: SwitchSecrets) -> Self {
         let sob = SwitchOidBuilder::new();
 
         let name = dialoguer::Input::<String>::new()
@@ -311,7 +567,8 @@ impl Switch {
             .unwrap();
 
         let ip = dialoguer::Input::<String>::new()
-            .with_prompt("IP")
+            .with_prompt("IP or hostname")
+            .validate_with(|input: &String| Switch::parse_host(input).map(|_| ()))
             .interact()
             .unwrap();
 
@@ -320,14 +577,12 @@ impl Switch {
             .interact()
             .unwrap();
 
-        let brand = sob.get_oid_name(
-            dialoguer::Select::new()
-                .with_prompt("Brand")
-                .items(sob.get_oid_names().as_slice())
-                .default(0)
-                .interact()
-                .unwrap(),
-        );
+        let stack_members = dialoguer::Input::<u64>::new()
+            .with_prompt("Stack members")
+            .default(1)
+            .validate_with(|input: &u64| Switch::validate_stack_members(Some(*input)))
+            .interact()
+            .unwrap();
 
         let keyring = dialoguer::Select::new()
             .with_prompt("Use system keystore for passwords? No will require you to input passwords on each run")
@@ -351,13 +606,13 @@ impl Switch {
             _ => unreachable!(),
         };
 
-        let credentials = collect_credentials(version, keyring, None);
+        let credentials = collect_credentials(version, keyring, None, &secrets);
 
-        Self {
+        let mut switch = Self {
             name,
             ip,
             ports,
-            brand,
+            stack_members: (stack_members > 1).then_some(stack_members),
             version,
             community: credentials.community,
             auth: credentials.auth,
@@ -366,6 +621,192 @@ impl Switch {
             auth_pass: credentials.password,
             encryption: credentials.encryption,
             encryption_pass: credentials.encryption_pass,
+            ..Default::default()
+        };
+
+        // The brand is asked for last so that it can be detected with the credentials above
+        let detected_brand = if dialoguer::Confirm::new()
+            .with_prompt("Detect the brand from the switch?")
+            .default(true)
+            .interact()
+            .unwrap()
+        {
+            match switch.identify().await {
+                Ok(identity) => {
+                    println!("{} reports: {}", switch.name, identity.sys_descr);
+                    if identity.detected_brand.is_none() {
+                        println!("Brand not recognized, pick one below");
+                    }
+                    identity.detected_brand
+                }
+                Err(e) => {
+                    println!("Could not detect the brand: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        switch.brand = sob.get_oid_name(
+            dialoguer::Select::new()
+                .with_prompt("Brand")
+                .items(sob.get_oid_names().as_slice())
+                .default(
+                    detected_brand
+                        .as_ref()
+                        .and_then(|brand| sob.get_oid_names().iter().position(|x| x == brand))
+                        .unwrap_or(0),
+                )
+                .interact()
+                .unwrap(),
+        );
+
+        if let Some(detected_brand) = detected_brand {
+            if detected_brand != switch.brand {
+                println!(
+                    "Warning: {} identifies as {}, not {}",
+                    switch.name, detected_brand, switch.brand
+                );
+            }
+        }
+
+        switch
+    }
+
+    pub fn from_args(args: SwitchArgs, switch_names: Vec<String>) -> Result<Self, String> {
+        let name = args.name.clone().ok_or("Name is required")?;
+
+        if switch_names.contains(&name) {
+            return Err(format!("Switch {} already exists", name));
+        }
+
+        let mut switch = Self {
+            name,
+            keyring: args.keyring.unwrap_or(true),
+            ..Default::default()
+        };
+        switch.apply_args(args)?;
+
+        Ok(switch)
+    }
+
+    pub fn apply_args(&mut self, args: SwitchArgs) -> Result<(), String> {
+        let mut updated = self.clone();
+
+        if let Some(ip) = args.ip {
+            updated.ip = ip;
+        }
+        if let Some(ports) = args.ports {
+            updated.ports = ports;
+        }
+        if let Some(stack_members) = args.stack_members {
+            updated.stack_members = (stack_members > 1).then_some(stack_members);
+        }
+        if let Some(brand) = args.brand {
+            let sob = SwitchOidBuilder::new();
+            updated.brand = sob
+                .get_oid_names()
+                .into_iter()
+                .find(|name| name.eq_ignore_ascii_case(&brand))
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown brand {}, expected one of: {}",
+                        brand,
+                        sob.get_oid_names().join(", ")
+                    )
+                })?;
+        }
+        if let Some(version) = args.version {
+            updated.version = version;
+        }
+        if let Some(keyring) = args.keyring {
+            updated.keyring = keyring;
+        }
+        if let Some(community) = args.community {
+            updated.community = community;
+        }
+        if let Some(auth) = args.auth {
+            updated.auth = auth;
+        }
+        if let Some(auth_user) = args.auth_user {
+            updated.auth_user = auth_user;
+        }
+        if let Some(auth_pass) = args.auth_pass {
+            updated.auth_pass = auth_pass;
+        }
+        if let Some(encryption) = args.encryption {
+            updated.encryption = encryption;
+        }
+        if let Some(encryption_pass) = args.encryption_pass {
+            updated.encryption_pass = encryption_pass;
+        }
+        updated.snmp = args.snmp.or(updated.snmp);
+        for port_label in args.labels {
+            if port_label.label.is_empty() {
+                updated.labels.remove(&port_label.port);
+            } else {
+                updated.labels.insert(port_label.port, port_label.label);
+            }
+        }
+
+        updated.validate()?;
+
+        if self.keyring && !updated.keyring {
+            self.remove_keys();
+        }
+
+        *self = updated;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.ip.is_empty() {
+            return Err("IP is required".to_string());
+        }
+        Switch::parse_host(&self.ip)?;
+        if self.ports == 0 {
+            return Err("Ports is required".to_string());
+        }
+        if self.brand.is_empty() {
+            return Err("Brand is required".to_string());
+        }
+        Switch::validate_stack_members(self.stack_members)?;
+
+        Switch::validate_labels(&self.labels)?;
+        self.snmp.validate()?;
+
+        match self.version {
+            SNMPVersion::V2 => {
+                if self.keyring && self.community.is_empty() {
+                    return Err("Community is required when using the keystore".to_string());
+                }
+            }
+            SNMPVersion::V3 => {
+                if self.auth_user.is_empty() {
+                    return Err("Username is required for SNMP v3".to_string());
+                }
+                if self.keyring && self.auth_pass.is_empty() {
+                    return Err("Auth password is required when using the keystore".to_string());
+                }
+                if self.keyring
+                    && self.encryption != SNMPEncryption::None
+                    && self.encryption_pass.is_empty()
+                {
+                    return Err(
+                        "Encryption password is required when using the keystore".to_string()
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_stack_members(stack_members: Option<u64>) -> Result<(), String> {
+        match stack_members {
+            Some(0) => Err("Stack members must be at least 1".to_string()),
+            _ => Ok(()),
         }
     }
 
@@ -415,135 +856,928 @@ impl Switch {
     //
     // Networking, OIDs, and ports
     //
-    pub(crate) fn get_socket_addr(&self) -> SocketAddr {
-        let ip_addr: IpAddr = self.ip.parse().expect("Invalid IP address");
-        SocketAddr::new(ip_addr, 161)
-    }
+    // The address may be an IP, a bracketed IPv6 address or a hostname that is resolved
+    // each time the switch is contacted
+    fn parse_host(host: &str) -> Result<Host, String> {
+        let host = host.trim();
+
+        if let Some(ipv6) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+            return ipv6
+                .parse::<Ipv6Addr>()
+                .map(|ip| Host::Ip(IpAddr::V6(ip)))
+                .map_err(|_| format!("Invalid IPv6 address: {}", host));
+        }
 
-    pub(crate) fn get_oid(&self) -> Vec<u64> {
-        let sob = SwitchOidBuilder::new();
-        let oid = sob
-            .get_switch_oid(&self.brand)
-            .expect("Invalid brand");
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(Host::Ip(ip));
+        }
 
-        // Split the oid by '.' and convert each part to a u32
-        oid.split('.').map(|x| x.parse::<u64>().unwrap()).collect()
-    }
+        // Anything that looks like an IPv4 address but did not parse is a typo, not a name
+        if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return Err(format!("Invalid IP address: {}", host));
+        }
 
-    pub(crate) fn get_ports(&self) -> Vec<u64> {
-        let ports_input = dialoguer::Input::<String>::new()
-            .with_prompt("List of ports (ex: 1-6,8,10-12)")
-            .default(format!("1-{}", self.ports))
-            .interact()
-            .unwrap();
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
 
-        Switch::parse_ports(ports_input).expect("Invalid port range")
+        if host.len() > 253 || !host.trim_end_matches('.').split('.').all(valid_label) {
+            return Err(format!("Invalid IP address or hostname: {}", host));
+        }
+
+        Ok(Host::Name(host.to_string()))
     }
 
-    pub(crate) fn parse_ports(ports_input: String) -> Result<Vec<u64>, String> {
-        let mut ports = Vec::new();
+    pub(crate) async fn resolve_socket_addr(&self) -> Result<SocketAddr, SnmpError> {
+        let port = self.get_snmp_settings().get_port();
 
-        for port in ports_input.split(',') {
-            if port.contains(' ') {
-                return Err(format!("Invalid port range: {}", ports_input));
-            }
+        match Switch::parse_host(&self.ip).map_err(SnmpError::SessionError)? {
+            Host::Ip(ip) => Ok(SocketAddr::new(ip, port)),
+            Host::Name(name) => tokio::net::lookup_host((name.as_str(), port))
+                .await
+                .map_err(|e| SnmpError::SessionError(format!("Could not resolve {}: {}", name, e)))?
+                .next()
+                .ok_or_else(|| SnmpError::SessionError(format!("No address found for {}", name))),
+        }
+    }
 
-            if port.contains('-') {
-                let range: Vec<&str> = port.split('-').collect();
+    pub(crate) fn get_retry_policy(&self) -> RetryPolicy {
+        self.get_snmp_settings().get_retry_policy()
+    }
 
-                if range.len() != 2 {
-                    return Err(format!("Invalid port range: {}", ports_input));
-                }
+    fn get_snmp_settings(&self) -> SnmpSettings {
+        self.snmp.or(self.snmp_defaults)
+    }
 
-                let start = range[0]
-                    .parse::<u64>()
-                    .map_err(|_| format!("Invalid port range: {}", ports_input))?;
+    pub(crate) fn set_snmp_defaults(&mut self, defaults: SnmpSettings) {
+        self.snmp_defaults = defaults;
+    }
 
-                let end = range[1]
-                    .parse::<u64>()
-                    .map_err(|_| format!("Invalid port range: {}", ports_input))?;
+    // The built-in brand or user profile the switch is addressed with
+    pub(crate) fn get_profile(&self) -> Result<SwitchOid, String> {
+        SwitchOidBuilder::new()
+            .get_profile(&self.brand)
+            .cloned()
+            .ok_or_else(|| self.unknown_brand())
+    }
 
-                for i in start..=end {
-                    ports.push(i);
-                }
-            } else {
-                ports.push(
-                    port.parse::<u64>()
-                        .map_err(|_| format!("Invalid port range: {}", ports_input))?,
-                );
-            }
-        }
+    // A profile that failed validation is not registered, so its switches have no brand
+    fn unknown_brand(&self) -> String {
+        format!(
+            "Switch {} uses brand {}, which is not a built-in brand or a valid profile",
+            self.name, self.brand
+        )
+    }
 
-        ports.sort();
-        ports.dedup();
-        Ok(ports)
+    pub(crate) fn get_brand(&self) -> &str {
+        &self.brand
     }
 
-    async fn set(&mut self, value: i64) -> std::io::Result<()> {
-        if !self.keyring {
-            match self.version {
-                SNMPVersion::V2 => {
-                    self.community = dialoguer::Input::<String>::new()
-                        .with_prompt("Community")
-                        .default(self.community.clone())
-                        .interact()
-                        .unwrap();
-                }
-                SNMPVersion::V3 => {
-                    self.auth_pass = dialoguer::Password::new()
-                        .with_prompt("Auth Password")
-                        .interact()
-                        .unwrap();
+    //
+    // Port labels
+    //
+    fn parse_labels(input: &str) -> Result<BTreeMap<SwitchPort, String>, String> {
+        let mut labels = BTreeMap::new();
 
-                    if self.encryption != SNMPEncryption::None {
-                        self.encryption_pass = dialoguer::Password::new()
-                            .with_prompt("Encryption Password")
-                            .interact()
-                            .unwrap();
-                    }
-                }
+        for port_label in input.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let port_label = PortLabel::from_str(port_label)?;
+            if !port_label.label.is_empty() {
+                labels.insert(port_label.port, port_label.label);
             }
         }
 
-        let client = Snmp::new();
-        let results = client.set(&self.clone(), value).await;
+        Ok(labels)
+    }
 
-        match results {
-            Ok(results) => {
-                println!("Status for {}:", self.name);
-                for result in results {
-                    println!("\t{}", result);
-                }
+    // Labels are used in place of port numbers, so they cannot look like a port range
+    fn validate_labels(labels: &BTreeMap<SwitchPort, String>) -> Result<(), String> {
+        let mut seen = Vec::new();
+
+        for (port, label) in labels {
+            if label.contains([',', ' ']) || Switch::parse_ports(label.clone()).is_ok() {
+                return Err(format!(
+                    "Invalid label {} for port {}, labels cannot be port numbers or contain commas or spaces",
+                    label, port
+                ));
             }
-            Err(e) => {
-                println!("Error getting status for {}: {}", self.name, e);
+
+            let lowercase = label.to_lowercase();
+            if seen.contains(&lowercase) {
+                return Err(format!("Label {} is used for more than one port", label));
             }
+            seen.push(lowercase);
         }
 
         Ok(())
     }
 
-    pub(crate) fn get_version(&self) -> SNMPVersion {
-        self.version
+    fn get_labels(&self) -> String {
+        self.labels
+            .iter()
+            .map(|(port, label)| format!("{}={}", port, label))
+            .collect::<Vec<String>>()
+            .join(",")
     }
 
-    //
-    // Key ring functions
-    //
-    pub(crate) fn remove_keys(&self) {
-        if self.keyring {
-            match self.version {
-                SNMPVersion::V2 => {
-                    keyring::remove_key(&self.name, keyring::KeyRingType::Community)
-                        .unwrap_or_else(|e| {
-                            println!(
-                                "Error removing {} string for {}: {}",
-                                keyring::KeyRingType::Community,
-                                self.name,
-                                e
-                            )
-                        });
-                }
+    // Labels and ports without a member are on the member addressed by the brand OID
+    pub(crate) fn get_label(&self, port: &SwitchPort) -> Option<String> {
+        let default_group = self.get_pse_group();
+        let group = port.group.unwrap_or(default_group);
+
+        self.labels
+            .iter()
+            .find(|(labelled, _)| {
+                labelled.port == port.port && labelled.group.unwrap_or(default_group) == group
+            })
+            .map(|(_, label)| label.clone())
+    }
+
+    // Replaces every entry of a port list that matches a label with its port number,
+    // entries are trimmed so that "uplink, ap" reads the same as "uplink,ap"
+    fn resolve_labels(&self, ports_input: &str) -> String {
+        ports_input
+            .split(',')
+            .map(str::trim)
+            .map(|entry| {
+                self.labels
+                    .iter()
+                    .find(|(_, label)| label.eq_ignore_ascii_case(entry))
+                    .map(|(port, _)| port.to_string())
+                    .unwrap_or_else(|| entry.to_string())
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub(crate) fn select_ports(&mut self, ports: Option<String>) {
+        self.selected_ports = ports;
+    }
+
+    pub(crate) fn get_ports(&self) -> Result<Vec<SwitchPort>, String> {
+        let ports_input = match &self.selected_ports {
+            Some(ports) => ports.clone(),
+            None if is_interactive() => dialoguer::Input::<String>::new()
+                .with_prompt("List of ports or port labels (ex: 1-6,8,10-12,lobby-camera)")
+                .default(self.get_default_ports())
+                .interact()
+                .unwrap(),
+            None => {
+                return Err("No ports given, use --ports when not running interactively".to_string())
+            }
+        };
+
+        let mut ports = Switch::parse_ports(self.resolve_labels(&ports_input))
+            .map_err(|_| format!("Invalid port range: {}", ports_input))?;
+
+        // Ports without a member are on the member addressed by the brand OID, and every
+        // port of a stacked switch is shown with its member
+        let groups = self.get_groups();
+        let default_group = self.get_pse_group();
+        for port in ports.iter_mut() {
+            let group = port.group.unwrap_or(default_group);
+            if !groups.contains(&group) {
+                return Err(format!(
+                    "Switch {} has no stack member {}, expected one of: {}",
+                    self.name,
+                    group,
+                    Switch::format_ports(&groups)
+                ));
+            }
+            port.group = (groups.len() > 1).then_some(group);
+        }
+        ports.sort();
+        ports.dedup();
+
+        if let Some(profile) = SwitchOidBuilder::new().get_profile(&self.brand) {
+            for port in &ports {
+                profile
+                    .get_port_oid(port.group, port.port)
+                    .map_err(|e| format!("{}: {}", self.name, e))?;
+            }
+        }
+
+        for port in &ports {
+            if let Some(discovered) = self.poe_ports.get(&port.group.unwrap_or(default_group)) {
+                if !discovered.contains(&port.port) {
+                    return Err(format!(
+                        "Port {} is not a PoE port on {}, expected one of: {}",
+                        port,
+                        self.name,
+                        Switch::format_ports(discovered)
+                    ));
+                }
+            }
+        }
+
+        Ok(ports)
+    }
+
+    // Writes a sorted port list as ranges, ex: 1-6,8,10-12
+    pub(crate) fn format_ports(ports: &[u64]) -> String {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+
+        for port in ports {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == *port => *end = *port,
+                _ => ranges.push((*port, *port)),
+            }
+        }
+
+        ranges
+            .iter()
+            .map(|(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    //
+    // Identification and PoE port discovery
+    //
+    // Reads sysObjectID and sysDescr and matches them against the known brands
+    pub async fn identify(&mut self) -> Result<SwitchIdentity, String> {
+        self.load_runtime_credentials()?;
+
+        let values = Snmp::new()
+            .get_values(self, &[&SYS_OBJECT_ID, &SYS_DESCR])
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let sys_object_id = match &values[0] {
+            SnmpValue::ObjectIdentifier(arcs) => arcs.clone(),
+            _ => vec![],
+        };
+        let sys_descr = values[1].to_string().trim().to_string();
+
+        Ok(SwitchIdentity {
+            switch: self.name.clone(),
+            sys_object_id: values[0].to_string(),
+            detected_brand: SwitchOidBuilder::new().detect_brand(&sys_object_id, &sys_descr),
+            sys_descr,
+            brand: self.brand.clone(),
+        })
+    }
+
+    // Walks pethPsePortAdminEnable and keeps the ports of each group, replacing any
+    // earlier discovery
+    pub async fn discover(&mut self) -> Result<(), String> {
+        self.load_runtime_credentials()?;
+
+        let entries = Snmp::new()
+            .walk(self, &PSE_PORT_ADMIN_ENABLE)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let poe_ports = Switch::group_poe_ports(entries);
+        if poe_ports.is_empty() {
+            return Err(format!("No PoE ports found on {}", self.name));
+        }
+
+        self.poe_ports = poe_ports;
+        Ok(())
+    }
+
+    // Rows are indexed by group and port, rows without a valid admin state are skipped
+    fn group_poe_ports(entries: Vec<(Vec<u64>, SnmpValue)>) -> BTreeMap<u64, Vec<u64>> {
+        let mut poe_ports: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+
+        for (index, value) in entries {
+            if let ([group, port], SnmpValue::Integer(1 | 2)) = (index.as_slice(), value) {
+                poe_ports.entry(*group).or_default().push(*port);
+            }
+        }
+
+        for ports in poe_ports.values_mut() {
+            ports.sort();
+            ports.dedup();
+        }
+
+        poe_ports
+    }
+
+    // PSE groups the ports can be on: the groups found by discover, else the configured
+    // stack members, else the group addressed by the brand OID
+    fn get_groups(&self) -> Vec<u64> {
+        if !self.poe_ports.is_empty() {
+            self.poe_ports.keys().copied().collect()
+        } else if let Some(members) = self.stack_members.filter(|members| *members > 1) {
+            (1..=members).collect()
+        } else {
+            vec![self.get_pse_group()]
+        }
+    }
+
+    // The discovered PoE ports or every port, of every member of a stacked switch
+    fn get_default_ports(&self) -> String {
+        let groups = self.get_groups();
+
+        groups
+            .iter()
+            .map(|group| {
+                let ports = match self.poe_ports.get(group) {
+                    Some(ports) => Switch::format_ports(ports),
+                    None => format!("1-{}", self.ports),
+                };
+
+                if groups.len() > 1 {
+                    ports
+                        .split(',')
+                        .map(|range| format!("{}:{}", group, range))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                } else {
+                    ports
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    // Vendor column with per-port power in milliwatts, when the brand has a valid one
+    pub(crate) fn get_port_power_oid(&self) -> Option<Vec<u64>> {
+        SwitchOidBuilder::new()
+            .get_port_power_oid(&self.brand)
+            .and_then(|oid| switch_oid::parse_oid(oid).ok())
+    }
+
+    // The brand OID ends with the PSE group its ports belong to
+    pub(crate) fn get_pse_group(&self) -> u64 {
+        SwitchOidBuilder::new()
+            .get_profile(&self.brand)
+            .map(SwitchOid::get_default_group)
+            .unwrap_or(1)
+    }
+
+    // Walks ifType and ifName and matches the ethernet interface names to ports, replacing
+    // any earlier mapping. Returns the number of ports mapped.
+    pub async fn map_interfaces(&mut self) -> Result<usize, String> {
+        self.load_runtime_credentials()?;
+
+        let types = self.walk(&IF_TYPE).await?;
+        let names = self.walk(&IF_NAME).await?;
+
+        let if_indexes = interface::map_ports(types, names, self.get_pse_group());
+        if if_indexes.is_empty() {
+            return Err(format!("No interface names on {} match a port", self.name));
+        }
+
+        self.if_indexes = if_indexes;
+        Ok(self.if_indexes.values().map(BTreeMap::len).sum())
+    }
+
+    pub(crate) fn get_if_index(&self, port: &SwitchPort) -> Option<u64> {
+        interface::find_if_index(&self.if_indexes, port, self.get_pse_group())
+    }
+
+    // Adds the interface of each port that answered, mapping interfaces first when they
+    // have not been yet. Interfaces are extra information, so failures are only reported.
+    async fn add_interfaces(&mut self, results: &mut [SwitchResult]) {
+        if self.if_indexes.is_empty() {
+            if let Err(e) = self.map_interfaces().await {
+                eprintln!("Could not map interfaces: {}", e);
+                return;
+            }
+        }
+
+        let mapped: Vec<(usize, u64)> = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.error.is_none())
+            .filter_map(|(index, result)| {
+                self.get_if_index(&result.get_port())
+                    .map(|if_index| (index, if_index))
+            })
+            .collect();
+        if mapped.is_empty() {
+            return;
+        }
+
+        let if_indexes: Vec<u64> = mapped.iter().map(|(_, if_index)| *if_index).collect();
+        match Snmp::new().get_interfaces(self, &if_indexes).await {
+            Ok(interfaces) => {
+                for ((index, _), interface) in mapped.into_iter().zip(interfaces) {
+                    let result = &mut results[index];
+                    result.interface = interface.name;
+                    result.description = interface.alias;
+                    result.link = interface.oper_status;
+                    result.speed_mbps = interface.speed_mbps;
+                }
+            }
+            Err(e) => eprintln!("Could not read interfaces: {}", e),
+        }
+    }
+
+    // Adds the LLDP neighbors seen on each port that answered. Neighbors are keyed by
+    // lldpRemLocalPortNum, which lldpLocPortTable names as an interface, so interfaces
+    // must be mapped to find their ports.
+    async fn add_neighbors(&mut self, results: &mut [SwitchResult]) {
+        if self.if_indexes.is_empty() {
+            if let Err(e) = self.map_interfaces().await {
+                eprintln!("Could not map interfaces, neighbors are not shown: {}", e);
+                return;
+            }
+        }
+
+        let snmp = Snmp::new();
+        let mut columns = Vec::new();
+        for column in [
+            &LLDP_LOC_PORT_ID[..],
+            &LLDP_LOC_PORT_DESC,
+            &IF_NAME,
+            &LLDP_REM_SYS_NAME,
+            &LLDP_REM_PORT_ID_SUBTYPE,
+            &LLDP_REM_PORT_ID,
+            &LLDP_REM_MAN_ADDR_IF_SUBTYPE,
+        ] {
+            match snmp.walk(self, column).await {
+                Ok(entries) => columns.push(entries),
+                Err(e) => {
+                    eprintln!("Could not read neighbors: {}", e);
+                    return;
+                }
+            }
+        }
+
+        let addresses = columns.pop().unwrap_or_default();
+        let port_ids = columns.pop().unwrap_or_default();
+        let port_id_subtypes = columns.pop().unwrap_or_default();
+        let names = columns.pop().unwrap_or_default();
+        let if_names = columns.pop().unwrap_or_default();
+        let local_port_descs = columns.pop().unwrap_or_default();
+        let local_port_ids = columns.pop().unwrap_or_default();
+
+        let local_ports = lldp::map_local_ports(local_port_ids, local_port_descs, if_names);
+        let mut neighbors: BTreeMap<SwitchPort, Vec<lldp::Neighbor>> = BTreeMap::new();
+        for (local_port, found) in
+            lldp::parse_neighbors(names, port_id_subtypes, port_ids, addresses)
+        {
+            let Some(port) = local_ports
+                .get(&local_port)
+                .and_then(|if_index| self.get_interface_port(*if_index))
+            else {
+                continue;
+            };
+            neighbors.entry(port).or_default().extend(found);
+        }
+
+        for result in results.iter_mut().filter(|result| result.error.is_none()) {
+            let Some(found) = neighbors.get(&self.normalize_port(result.get_port())) else {
+                continue;
+            };
+
+            let join = |values: Vec<String>| (!values.is_empty()).then(|| values.join("; "));
+            result.neighbor = join(
+                found
+                    .iter()
+                    .map(|neighbor| neighbor.name.clone().unwrap_or("Unnamed".to_string()))
+                    .collect(),
+            );
+            result.neighbor_port = join(found.iter().filter_map(|n| n.port_id.clone()).collect());
+            result.neighbor_address = join(
+                found
+                    .iter()
+                    .flat_map(|neighbor| neighbor.addresses.clone())
+                    .collect(),
+            );
+        }
+    }
+
+    async fn walk(&self, column: &[u64]) -> Result<Vec<(Vec<u64>, SnmpValue)>, String> {
+        Snmp::new()
+            .walk(self, column)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // Finds the ports the switch has learned a MAC address on. Credentials must be loaded.
+    pub async fn locate_mac(&mut self, mac: &MacAddress) -> Result<Vec<MacLocation>, String> {
+        if self.if_indexes.is_empty() {
+            if let Err(e) = self.map_interfaces().await {
+                eprintln!("Could not map interfaces on {}: {}", self.name, e);
+            }
+        }
+
+        let mut fdb = ForwardingTable::from_dot1q(self.walk(&DOT1Q_TP_FDB_PORT).await?);
+        if fdb.is_empty() {
+            fdb = ForwardingTable::from_dot1d(self.walk(&DOT1D_TP_FDB_PORT).await?);
+        }
+
+        let found = fdb.find(mac);
+        if found.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Without a bridge port map the bridge port is taken to be the ifIndex
+        let bridge_ports = bridge::parse_bridge_ports(self.walk(&DOT1D_BASE_PORT_IF_INDEX).await?);
+        let vlans = if fdb.has_fdb_ids() {
+            bridge::parse_vlan_fdb_ids(self.walk(&DOT1Q_VLAN_FDB_ID).await?)
+        } else {
+            BTreeMap::new()
+        };
+
+        // Ports are shown with their member on stacked switches only, like get_ports does
+        let stacked = self.get_groups().len() > 1;
+        let locations = found
+            .into_iter()
+            .map(|entry| {
+                let if_index = bridge_ports
+                    .get(&entry.bridge_port)
+                    .copied()
+                    .unwrap_or(entry.bridge_port);
+                let port = interface::find_port(&self.if_indexes, if_index);
+
+                MacLocation {
+                    switch: self.name.clone(),
+                    group: port.and_then(|(group, _)| stacked.then_some(group)),
+                    port: port.map(|(_, port)| port),
+                    if_index,
+                    vlan: entry
+                        .fdb_id
+                        .map(|fdb_id| vlans.get(&fdb_id).copied().unwrap_or(fdb_id)),
+                    macs_on_port: fdb.macs_on(entry.bridge_port),
+                }
+            })
+            .collect();
+
+        Ok(locations)
+    }
+
+    // The port an interface belongs to, with its member on stacked switches
+    fn get_interface_port(&self, if_index: u64) -> Option<SwitchPort> {
+        let (group, port) = interface::find_port(&self.if_indexes, if_index)?;
+
+        Some(self.normalize_port(SwitchPort {
+            group: Some(group),
+            port,
+        }))
+    }
+
+    // Ports on the default group compare equal with and without their group
+    fn normalize_port(&self, port: SwitchPort) -> SwitchPort {
+        let group = port.group.unwrap_or(self.get_pse_group());
+
+        SwitchPort {
+            group: (group != self.get_pse_group()).then_some(group),
+            port: port.port,
+        }
+    }
+
+    pub(crate) fn select_neighbors(&mut self, show_neighbors: bool) {
+        self.show_neighbors = show_neighbors;
+    }
+
+    pub(crate) fn select_interfaces(&mut self, show_interfaces: bool) {
+        self.show_interfaces = show_interfaces;
+    }
+
+    pub(crate) fn copy_interfaces(&mut self, other: &Switch) {
+        self.if_indexes = other.if_indexes.clone();
+    }
+
+    pub(crate) fn has_interfaces(&self) -> bool {
+        !self.if_indexes.is_empty()
+    }
+
+    pub(crate) fn get_poe_ports(&self) -> String {
+        self.poe_ports
+            .iter()
+            .map(|(group, ports)| format!("group {}: {}", group, Switch::format_ports(ports)))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+
+    // Ports on a stack member are given as 2:14, 2/14 or 2/0/14, and a range ends on
+    // the member it starts on, ex: 1-6,8,2:1-4,2/0/10-2/0/12
+    pub(crate) fn parse_ports(ports_input: String) -> Result<Vec<SwitchPort>, String> {
+        let invalid = || format!("Invalid port range: {}", ports_input);
+        let mut ports = Vec::new();
+
+        for port in ports_input.split(',') {
+            if port.contains(' ') {
+                return Err(invalid());
+            }
+
+            let (start, end) = match port.split_once('-') {
+                Some((start, end)) => (
+                    Switch::parse_port(start).ok_or_else(invalid)?,
+                    Switch::parse_port(end).ok_or_else(invalid)?,
+                ),
+                None => {
+                    let port = Switch::parse_port(port).ok_or_else(invalid)?;
+                    (port, port)
+                }
+            };
+
+            if end.group.is_some() && end.group != start.group || start.port > end.port {
+                return Err(invalid());
+            }
+
+            for port in start.port..=end.port {
+                ports.push(SwitchPort {
+                    group: start.group,
+                    port,
+                });
+            }
+        }
+
+        ports.sort();
+        ports.dedup();
+        Ok(ports)
+    }
+
+    // The middle number of 2/0/14 is the module, only the built-in module 0 has PoE ports
+    fn parse_port(input: &str) -> Option<SwitchPort> {
+        let (group, port) = match input.split_once(':') {
+            Some((group, port)) => (Some(group), port),
+            None => match input.split('/').collect::<Vec<&str>>().as_slice() {
+                [port] => (None, *port),
+                [group, port] | [group, "0", port] => (Some(*group), *port),
+                _ => return None,
+            },
+        };
+
+        Some(SwitchPort {
+            group: group.map(str::parse::<u64>).transpose().ok()?,
+            port: port.parse::<u64>().ok()?,
+        })
+    }
+
+    pub(crate) async fn power_off(&mut self) -> std::io::Result<Vec<SwitchResult>> {
+        let off = SwitchOidBuilder::new()
+            .get_off(&self.brand)
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, self.unknown_brand())
+            })?;
+
+        self.set_ports(off).await
+    }
+
+    pub(crate) async fn power_on(&mut self) -> std::io::Result<Vec<SwitchResult>> {
+        let on = SwitchOidBuilder::new().get_on(&self.brand).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, self.unknown_brand())
+        })?;
+
+        self.set_ports(on).await
+    }
+
+    pub(crate) async fn port_status(&mut self) -> std::io::Result<Vec<SwitchResult>> {
+        let ports = self
+            .prepare()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let client = Snmp::new();
+        let results = match client.get(&self.clone(), &ports).await {
+            Ok((mut results, pse)) => {
+                self.pse = pse;
+                self.add_pse_status(&mut results);
+                if self.show_interfaces {
+                    self.add_interfaces(&mut results).await;
+                }
+                if self.show_neighbors {
+                    self.add_neighbors(&mut results).await;
+                }
+                Ok(results)
+            }
+            Err(e) => Err(e),
+        };
+
+        Ok(self.collect_results(results, &ports))
+    }
+
+    // Each port carries the budget of the PSE it draws from, so that the json, yaml and
+    // csv records hold what the table shows below the ports
+    fn add_pse_status(&self, results: &mut [SwitchResult]) {
+        let default_group = self.get_pse_group();
+
+        for result in results.iter_mut() {
+            let group = result.group.unwrap_or(default_group);
+            if let Some(pse) = self.pse.iter().find(|pse| pse.group == group) {
+                result.pse_status = Some(pse.status.clone());
+                result.pse_power_w = pse.power;
+                result.pse_consumption_w = pse.consumption;
+            }
+        }
+    }
+
+    async fn set_ports(&mut self, value: i64) -> std::io::Result<Vec<SwitchResult>> {
+        let ports = self
+            .prepare()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let client = Snmp::new();
+        let results = client.set(&self.clone(), value, &ports).await;
+
+        Ok(self.collect_results(results, &ports))
+    }
+
+    // Loads credentials and pins the port selection, so any prompts happen
+    // before SNMP work starts and later steps act on the same ports
+    pub(crate) fn prepare(&mut self) -> Result<Vec<SwitchPort>, String> {
+        self.get_profile()?;
+        self.load_runtime_credentials()?;
+
+        let ports = self.get_ports()?;
+        self.select_ports(Some(
+            ports
+                .iter()
+                .map(|port| port.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+        ));
+
+        Ok(ports)
+    }
+
+    // Ports are only powered back on once all of them are off
+    pub async fn cycle(&mut self, delay: Duration) -> std::io::Result<()> {
+        self.disable().await?;
+
+        if self.output == OutputFormat::Table {
+            println!("Waiting {}s before powering on", delay.as_secs_f64());
+        }
+        tokio::time::sleep(delay).await;
+
+        self.enable().await
+    }
+
+    // Like cycle, but returns the results of the last step instead of reporting them
+    pub(crate) async fn power_cycle(
+        &mut self,
+        delay: Duration,
+    ) -> std::io::Result<Vec<SwitchResult>> {
+        let off_results = self.power_off().await?;
+        if self.check_powered_off(&off_results).is_err() {
+            return Ok(off_results);
+        }
+
+        if self.output == OutputFormat::Table {
+            println!(
+                "Powered off {}, waiting {}s before powering on",
+                self.name,
+                delay.as_secs_f64()
+            );
+        }
+        tokio::time::sleep(delay).await;
+
+        self.power_on().await
+    }
+
+    fn check_powered_off(&self, results: &[SwitchResult]) -> std::io::Result<()> {
+        let failed: Vec<String> = results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .map(|result| result.get_port().to_string())
+            .collect();
+
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        Err(std::io::Error::other(format!(
+            "Could not power off ports {} on {}",
+            failed.join(","),
+            self.name
+        )))
+    }
+
+    // A failure that affects the whole switch is reported against every requested port
+    fn collect_results(
+        &self,
+        results: Result<Vec<SwitchResult>, SnmpError>,
+        ports: &[SwitchPort],
+    ) -> Vec<SwitchResult> {
+        let mut results = results.unwrap_or_else(|e| {
+            ports
+                .iter()
+                .map(|port| SwitchResult::error(&self.name, *port, &e))
+                .collect()
+        });
+
+        for result in results.iter_mut() {
+            result.label = self.get_label(&result.get_port());
+        }
+
+        results
+    }
+
+    fn report(&self, results: &[SwitchResult]) {
+        match self.output {
+            OutputFormat::Table => {
+                println!("Status for {}:", self.name);
+                for result in results {
+                    println!("\t{}", result);
+                }
+                for pse in &self.pse {
+                    println!("\t{}", pse);
+                }
+            }
+            format => output::print_records(format, results),
+        }
+    }
+
+    pub(crate) fn select_secrets(&mut self, secrets: SwitchSecrets) {
+        self.given_secrets = secrets;
+    }
+
+    pub(crate) fn select_output(&mut self, output: OutputFormat) {
+        self.output = output;
+    }
+
+    pub fn to_record(&self) -> SwitchRecord {
+        let v3 = self.version == SNMPVersion::V3;
+
+        SwitchRecord {
+            name: self.name.clone(),
+            ip: self.ip.clone(),
+            brand: self.brand.clone(),
+            ports: self.ports,
+            stack_members: self.stack_members,
+            keyring: self.keyring,
+            version: self.version.to_string(),
+            username: v3.then(|| self.auth_user.clone()),
+            auth: v3.then(|| self.auth.to_string()),
+            encryption: v3.then(|| self.encryption.to_string()),
+            labels: (!self.labels.is_empty()).then(|| self.get_labels()),
+            poe_ports: (!self.poe_ports.is_empty()).then(|| self.get_poe_ports()),
+            snmp_port: self.snmp.port,
+            timeout: self.snmp.timeout,
+            retries: self.snmp.retries,
+        }
+    }
+
+    // Credentials that are not kept in the keystore are read from the RACKCLI_* environment
+    // variables, or prompted for when running interactively. Credentials that are already
+    // loaded are kept, so commands that talk to the switch more than once only ask once.
+    pub(crate) fn load_runtime_credentials(&mut self) -> Result<(), String> {
+        if self.keyring {
+            return Ok(());
+        }
+
+        match self.version {
+            SNMPVersion::V2 => {
+                if self.community.is_empty() {
+                    self.community = runtime_secret("RACKCLI_COMMUNITY", || {
+                        dialoguer::Input::<String>::new()
+                            .with_prompt("Community")
+                            .interact()
+                            .unwrap()
+                    })?;
+                }
+            }
+            SNMPVersion::V3 => {
+                if self.auth_pass.is_empty() {
+                    self.auth_pass = runtime_secret("RACKCLI_AUTH_PASSWORD", || {
+                        dialoguer::Password::new()
+                            .with_prompt("Auth Password")
+                            .interact()
+                            .unwrap()
+                    })?;
+                }
+
+                if self.encryption != SNMPEncryption::None && self.encryption_pass.is_empty() {
+                    self.encryption_pass = runtime_secret("RACKCLI_ENCRYPTION_PASSWORD", || {
+                        dialoguer::Password::new()
+                            .with_prompt("Encryption Password")
+                            .interact()
+                            .unwrap()
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn get_version(&self) -> SNMPVersion {
+        self.version
+    }
+
+    //
+    // Key ring functions
+    //
+    pub(crate) fn remove_keys(&self) {
+        if self.keyring {
+            match self.version {
+                SNMPVersion::V2 => {
+                    keyring::remove_key(&self.name, keyring::KeyRingType::Community)
+                        .unwrap_or_else(|e| {
+                            println!(
+                                "Error removing {} string for {}: {}",
+                                keyring::KeyRingType::Community,
+                                self.name,
+                                e
+                            )
+                        });
+                }
                 SNMPVersion::V3 => {
                     keyring::remove_key(&self.name, keyring::KeyRingType::Auth).unwrap_or_else(
                         |e| {
@@ -656,10 +1890,117 @@ impl Switch {
 
 impl std::fmt::Display for SwitchResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.status == STATUS_ON {
-            write!(f, "Port: {:2} - {}", self.port, self.status.green())
+        if let Some(label) = &self.label {
+            write!(f, "Port: {:>2} ({}) - ", self.get_port(), label)?;
         } else {
-            write!(f, "Port: {:2} - {}", self.port, self.status.red())
+            write!(f, "Port: {:>2} - ", self.get_port())?;
+        }
+
+        if let Some(error) = &self.error {
+            return write!(f, "{}: {}", self.status.red(), error);
+        } else if self.status == STATUS_ON {
+            write!(f, "{}", self.status.green())?;
+        } else {
+            write!(f, "{}", self.status.red())?;
+        }
+
+        // An enabled port that is searching has nothing connected, a fault needs a look
+        match self.detection.as_deref() {
+            Some(detection @ DETECTION_DELIVERING) => write!(f, ", {}", detection.green())?,
+            Some(detection @ (DETECTION_FAULT | DETECTION_OTHER_FAULT)) => {
+                write!(f, ", {}", detection.red())?
+            }
+            Some(detection) => write!(f, ", {}", detection)?,
+            None => {}
+        }
+
+        if let Some(power_class) = &self.power_class {
+            write!(f, ", {}", power_class)?;
+        }
+
+        if let Some(power_mw) = self.power_mw {
+            write!(f, ", {:.1} W ({} mW)", power_mw as f64 / 1000.0, power_mw)?;
+        }
+
+        if let Some(interface) = &self.interface {
+            write!(f, " | {}", interface)?;
+            if let Some(description) = &self.description {
+                write!(f, " \"{}\"", description)?;
+            }
+        }
+
+        match self.link.as_deref() {
+            Some(link @ "Up") => write!(f, ", {}", link.green())?,
+            Some(link) => write!(f, ", {}", link.red())?,
+            None => {}
+        }
+
+        if let Some(speed_mbps) = self.speed_mbps.filter(|speed| *speed > 0) {
+            write!(f, ", {} Mb/s", speed_mbps)?;
+        }
+
+        if let Some(neighbor) = &self.neighbor {
+            write!(f, " | Neighbor: {}", neighbor)?;
+            if let Some(port) = &self.neighbor_port {
+                write!(f, " port {}", port)?;
+            }
+            if let Some(address) = &self.neighbor_address {
+                write!(f, " at {}", address)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SwitchPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.group {
+            Some(group) => f.pad(&format!("{}/{}", group, self.port)),
+            None => f.pad(&self.port.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for PseStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PSE {}: {}", self.group, self.status)?;
+
+        match (self.consumption, self.power) {
+            (Some(consumption), Some(power)) if power > 0 => write!(
+                f,
+                ", {} W of {} W used ({}%)",
+                consumption,
+                power,
+                consumption * 100 / power
+            ),
+            (Some(consumption), _) => write!(f, ", {} W used", consumption),
+            (None, Some(power)) => write!(f, ", {} W available", power),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for SwitchIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Identity of {}:", self.switch)?;
+        writeln!(f, "  sysObjectID: {}", self.sys_object_id)?;
+        writeln!(f, "  sysDescr: {}", self.sys_descr)?;
+
+        match &self.detected_brand {
+            Some(brand) if *brand == self.brand => writeln!(f, "  Brand: {}", brand.green()),
+            Some(brand) => writeln!(
+                f,
+                "  Brand: {} ({} is configured)",
+                brand.yellow(),
+                self.brand
+            ),
+            None => writeln!(
+                f,
+                "  Brand: {} ({} is configured)",
+                "Unknown".red(),
+                self.brand
+            ),
         }
     }
 }
@@ -705,44 +2046,83 @@ impl std::fmt::Display for Switch {
                 f,
                 "  Name: {}\n  Addr: {}\n  Brand: {}\n  Ports: {}\n  Keyring: {}\n  Version: {}\n  Community: {}\n",
                 self.name, self.ip, self.brand, self.ports, self.keyring, self.version, self.community
-            )
+            )?;
         } else {
             write!(
                 f,
                 "  Name: {}\n  Addr: {}\n  Brand: {}\n  Ports: {}\n  Keyring: {}\n  Version: {}\n  Username: {}\n  Auth: {}\n  Encryption: {}\n",
                 self.name, self.ip, self.brand, self.ports, self.keyring, self.version, self.auth_user, self.auth, self.encryption
-            )
+            )?;
+        }
+
+        if let Some(stack_members) = self.stack_members {
+            writeln!(f, "  Stack Members: {}", stack_members)?;
+        }
+
+        if !self.labels.is_empty() {
+            writeln!(f, "  Labels: {}", self.get_labels())?;
+        }
+
+        if !self.poe_ports.is_empty() {
+            writeln!(f, "  PoE Ports: {}", self.get_poe_ports())?;
+        }
+
+        if !self.snmp.is_empty() {
+            writeln!(f, "  SNMP: {}", self.snmp)?;
         }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    pub(super) fn port_list(ports: &[u64]) -> Vec<SwitchPort> {
+        ports.iter().map(|port| SwitchPort::from(*port)).collect()
+    }
+
+    // The switch the tests of switches, groups and nodes are run against
+    pub(crate) fn v2_args() -> SwitchArgs {
+        SwitchArgs {
+            name: Some("core".to_string()),
+            ip: Some("10.0.0.2".to_string()),
+            brand: Some("netgear".to_string()),
+            ports: Some(24),
+            version: Some(SNMPVersion::V2),
+            community: Some("public".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn switches() -> Vec<Switch> {
+        vec![Switch::from_args(v2_args(), vec![]).unwrap()]
+    }
+
     // Valid input tests
     #[test]
     fn test_parse_ports_single() {
         let ports = Switch::parse_ports("1".to_string());
-        assert_eq!(ports, Ok(vec![1]));
+        assert_eq!(ports, Ok(port_list(&[1])));
     }
 
     #[test]
     fn test_parse_ports_range() {
         let ports = Switch::parse_ports("1-6".to_string());
-        assert_eq!(ports, Ok(vec![1, 2, 3, 4, 5, 6]));
+        assert_eq!(ports, Ok(port_list(&[1, 2, 3, 4, 5, 6])));
     }
 
     #[test]
     fn test_parse_mixed_input() {
         let ports = Switch::parse_ports("1-6,8,10-12".to_string());
-        assert_eq!(ports, Ok(vec![1, 2, 3, 4, 5, 6, 8, 10, 11, 12]));
+        assert_eq!(ports, Ok(port_list(&[1, 2, 3, 4, 5, 6, 8, 10, 11, 12])));
     }
 
     #[test]
     fn test_parse_duplicate_input() {
         let ports = Switch::parse_ports("1-6,8,10-12,1,2,3,4,5,6,8,10,11,12".to_string());
-        assert_eq!(ports, Ok(vec![1, 2, 3, 4, 5, 6, 8, 10, 11, 12]));
+        assert_eq!(ports, Ok(port_list(&[1, 2, 3, 4, 5, 6, 8, 10, 11, 12])));
     }
 
     // Invalid input tests
@@ -769,10 +2149,479 @@ mod tests {
         let ports = Switch::parse_ports("1-6,a".to_string());
         assert_eq!(ports, Err("Invalid port range: 1-6,a".to_string()));
     }
+
+    // Port label tests
+    fn labelled_switch() -> Switch {
+        Switch {
+            labels: BTreeMap::from([
+                (SwitchPort::from(3), "lobby-camera".to_string()),
+                (SwitchPort::from(5), "ap-2".to_string()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_port_label_from_str() {
+        assert_eq!(
+            PortLabel::from_str("3=lobby-camera"),
+            Ok(PortLabel {
+                port: SwitchPort::from(3),
+                label: "lobby-camera".to_string()
+            })
+        );
+        assert!(PortLabel::from_str("lobby-camera").is_err());
+    }
+
+    #[test]
+    fn test_get_ports_with_labels() {
+        let mut switch = labelled_switch();
+        switch.select_ports(Some("1,lobby-camera,AP-2".to_string()));
+        assert_eq!(switch.get_ports(), Ok(port_list(&[1, 3, 5])));
+    }
+
+    #[test]
+    fn test_port_label_from_str_with_member() {
+        assert_eq!(
+            PortLabel::from_str("2/5=ap-2").map(|port_label| port_label.port),
+            Ok(SwitchPort {
+                group: Some(2),
+                port: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_label_stacked() {
+        let mut switch = labelled_switch();
+        switch.stack_members = Some(2);
+        switch.labels.insert(
+            SwitchPort {
+                group: Some(2),
+                port: 5,
+            },
+            "ap-3".to_string(),
+        );
+        let port = |group, port| SwitchPort {
+            group: Some(group),
+            port,
+        };
+        assert_eq!(
+            switch.get_label(&port(1, 3)),
+            Some("lobby-camera".to_string())
+        );
+        assert_eq!(switch.get_label(&port(2, 5)), Some("ap-3".to_string()));
+        assert_eq!(switch.get_label(&port(2, 3)), None);
+
+        switch.select_ports(Some("lobby-camera,ap-3".to_string()));
+        assert_eq!(switch.get_ports(), Ok(vec![port(1, 3), port(2, 5)]));
+    }
+
+    #[test]
+    fn test_labels_in_config() {
+        let mut switch = labelled_switch();
+        switch.labels.insert(
+            SwitchPort {
+                group: Some(2),
+                port: 5,
+            },
+            "ap-3".to_string(),
+        );
+        let text = toml::to_string(&switch).unwrap();
+        assert!(text.contains("\"2/5\" = \"ap-3\""));
+        let read: Switch = toml::from_str(&text).unwrap();
+        assert_eq!(read.labels, switch.labels);
+    }
+
+    #[test]
+    fn test_get_ports_with_spaced_labels() {
+        let mut switch = labelled_switch();
+        switch.select_ports(Some("lobby-camera, AP-2 ,1".to_string()));
+        assert_eq!(switch.get_ports(), Ok(port_list(&[1, 3, 5])));
+    }
+
+    #[test]
+    fn test_get_ports_unknown_label() {
+        let mut switch = labelled_switch();
+        switch.select_ports(Some("1,garage".to_string()));
+        assert_eq!(
+            switch.get_ports(),
+            Err("Invalid port range: 1,garage".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_labels_rejects_port_range() {
+        let labels = BTreeMap::from([(SwitchPort::from(3), "1-4".to_string())]);
+        assert!(Switch::validate_labels(&labels).is_err());
+    }
+
+    #[test]
+    fn test_validate_labels_rejects_duplicates() {
+        let labels = BTreeMap::from([
+            (SwitchPort::from(3), "ap".to_string()),
+            (SwitchPort::from(4), "AP".to_string()),
+        ]);
+        assert_eq!(
+            Switch::validate_labels(&labels),
+            Err("Label AP is used for more than one port".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_args_labels() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        let args = SwitchArgs {
+            labels: vec![
+                PortLabel::from_str("3=lobby-camera").unwrap(),
+                PortLabel::from_str("4=ap-1").unwrap(),
+            ],
+            ..Default::default()
+        };
+        switch.apply_args(args).unwrap();
+        let args = SwitchArgs {
+            labels: vec![PortLabel::from_str("4=").unwrap()],
+            ..Default::default()
+        };
+        switch.apply_args(args).unwrap();
+        assert_eq!(switch.get_labels(), "3=lobby-camera");
+    }
+
+    // Selected ports tests
+    #[test]
+    fn test_get_ports_selected() {
+        let mut switch = Switch::default();
+        switch.select_ports(Some("1-3,8".to_string()));
+        assert_eq!(switch.get_ports(), Ok(port_list(&[1, 2, 3, 8])));
+    }
+
+    #[test]
+    fn test_get_ports_selected_invalid() {
+        let mut switch = Switch::default();
+        switch.select_ports(Some("1-A".to_string()));
+        assert_eq!(
+            switch.get_ports(),
+            Err("Invalid port range: 1-A".to_string())
+        );
+    }
+
+    // Cycle tests
+    #[test]
+    fn test_check_powered_off() {
+        let switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        let error = SnmpError::OperationError("timeout".to_string());
+
+        let results = vec![
+            SwitchResult::new("core", 1, "Off".to_string()),
+            SwitchResult::new("core", 2, "Off".to_string()),
+        ];
+        assert!(switch.check_powered_off(&results).is_ok());
+
+        let results = vec![
+            SwitchResult::new("core", 1, "Off".to_string()),
+            SwitchResult::error("core", 2, &error),
+            SwitchResult::error("core", 3, &error),
+        ];
+        assert_eq!(
+            switch.check_powered_off(&results).unwrap_err().to_string(),
+            "Could not power off ports 2,3 on core"
+        );
+    }
+
+    // PSE status tests
+    #[test]
+    fn test_add_pse_status() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        switch.pse = vec![PseStatus {
+            group: 1,
+            status: "On".to_string(),
+            power: Some(370),
+            consumption: Some(45),
+        }];
+
+        let mut results = vec![
+            SwitchResult::new("core", 1, "On".to_string()),
+            SwitchResult::new(
+                "core",
+                SwitchPort {
+                    group: Some(2),
+                    port: 1,
+                },
+                "On".to_string(),
+            ),
+        ];
+        switch.add_pse_status(&mut results);
+
+        assert_eq!(results[0].pse_status, Some("On".to_string()));
+        assert_eq!(results[0].pse_power_w, Some(370));
+        assert_eq!(results[0].pse_consumption_w, Some(45));
+        assert_eq!(results[1].pse_status, None);
+    }
+
+    #[test]
+    fn test_get_port_power_oid() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        assert_eq!(
+            switch.get_port_power_oid(),
+            Some(vec![1, 3, 6, 1, 4, 1, 4526, 10, 15, 1, 1, 1, 2])
+        );
+
+        switch.brand = "TP-Link".to_string();
+        assert_eq!(switch.get_port_power_oid(), None);
+    }
+
+    // Non-interactive create tests
+    #[test]
+    fn test_from_args_valid() {
+        let switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        assert_eq!(switch.name, "core");
+        assert_eq!(switch.brand, "Netgear");
+        assert!(switch.keyring);
+    }
+
+    #[test]
+    fn test_from_args_duplicate_name() {
+        let switch = Switch::from_args(v2_args(), vec!["core".to_string()]);
+        assert_eq!(switch.err(), Some("Switch core already exists".to_string()));
+    }
+
+    #[test]
+    fn test_from_args_unknown_brand() {
+        let args = SwitchArgs {
+            brand: Some("Acme".to_string()),
+            ..v2_args()
+        };
+        assert!(Switch::from_args(args, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_from_args_v3_requires_password() {
+        let args = SwitchArgs {
+            version: Some(SNMPVersion::V3),
+            auth_user: Some("admin".to_string()),
+            ..v2_args()
+        };
+        assert_eq!(
+            Switch::from_args(args, vec![]).err(),
+            Some("Auth password is required when using the keystore".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_args_keeps_unset_fields() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        let args = SwitchArgs {
+            ports: Some(48),
+            ..Default::default()
+        };
+        switch.apply_args(args).unwrap();
+        assert_eq!(switch.ports, 48);
+        assert_eq!(switch.ip, "10.0.0.2");
+    }
+
+    #[test]
+    fn test_apply_args_snmp_settings() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        let args = SwitchArgs {
+            snmp: SnmpSettings {
+                port: Some(1161),
+                retries: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        switch.apply_args(args).unwrap();
+        switch.set_snmp_defaults(SnmpSettings {
+            timeout: Some(3),
+            retries: Some(5),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            switch.get_retry_policy(),
+            RetryPolicy {
+                timeout: Duration::from_secs(3),
+                retries: 2,
+            }
+        );
+        assert_eq!(switch.get_snmp_settings().get_port(), 1161);
+    }
+
+    #[test]
+    fn test_parse_host() {
+        assert!(matches!(
+            Switch::parse_host("10.0.0.2"),
+            Ok(Host::Ip(IpAddr::V4(_)))
+        ));
+        assert!(matches!(
+            Switch::parse_host("fe80::1"),
+            Ok(Host::Ip(IpAddr::V6(_)))
+        ));
+        assert!(matches!(
+            Switch::parse_host("[2001:db8::10]"),
+            Ok(Host::Ip(IpAddr::V6(_)))
+        ));
+        assert!(matches!(
+            Switch::parse_host("poe-1.lab.example"),
+            Ok(Host::Name(name)) if name == "poe-1.lab.example"
+        ));
+    }
+
+    #[test]
+    fn test_parse_host_invalid() {
+        assert!(Switch::parse_host("10.0.0.300").is_err());
+        assert!(Switch::parse_host("[10.0.0.2]").is_err());
+        assert!(Switch::parse_host("bad host").is_err());
+        assert!(Switch::parse_host("-switch").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_socket_addr() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        switch.ip = "[::1]".to_string();
+        switch.set_snmp_defaults(SnmpSettings {
+            port: Some(1161),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            switch.resolve_socket_addr().await.unwrap(),
+            "[::1]:1161".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_ports_stack_members() {
+        let on_member = |group, ports: &[u64]| -> Vec<SwitchPort> {
+            ports
+                .iter()
+                .map(|port| SwitchPort {
+                    group: Some(group),
+                    port: *port,
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            Switch::parse_ports("2:14".to_string()),
+            Ok(on_member(2, &[14]))
+        );
+        assert_eq!(
+            Switch::parse_ports("2/14".to_string()),
+            Ok(on_member(2, &[14]))
+        );
+        assert_eq!(
+            Switch::parse_ports("2/0/14".to_string()),
+            Ok(on_member(2, &[14]))
+        );
+        assert_eq!(
+            Switch::parse_ports("3:1-2,3/0/7-3/0/8".to_string()),
+            Ok(on_member(3, &[1, 2, 7, 8]))
+        );
+        assert!(Switch::parse_ports("2:1-3:5".to_string()).is_err());
+        assert!(Switch::parse_ports("2/1/14".to_string()).is_err());
+        assert!(Switch::parse_ports("a:14".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_get_ports_stacked() {
+        let mut switch = Switch::from_args(
+            SwitchArgs {
+                stack_members: Some(2),
+                ..v2_args()
+            },
+            vec![],
+        )
+        .unwrap();
+
+        switch.select_ports(Some("1-2,2:3,1/2".to_string()));
+        assert_eq!(
+            switch.get_ports(),
+            Ok(vec![
+                SwitchPort {
+                    group: Some(1),
+                    port: 1
+                },
+                SwitchPort {
+                    group: Some(1),
+                    port: 2
+                },
+                SwitchPort {
+                    group: Some(2),
+                    port: 3
+                },
+            ])
+        );
+        assert_eq!(switch.get_default_ports(), "1:1-24,2:1-24");
+
+        switch.select_ports(Some("3:1".to_string()));
+        assert_eq!(
+            switch.get_ports(),
+            Err("Switch core has no stack member 3, expected one of: 1-2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_ports_not_stacked() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+
+        switch.select_ports(Some("1:4".to_string()));
+        assert_eq!(switch.get_ports(), Ok(port_list(&[4])));
+
+        switch.select_ports(Some("2:4".to_string()));
+        assert!(switch.get_ports().is_err());
+    }
+
+    #[test]
+    fn test_switch_port_display() {
+        let port = SwitchPort {
+            group: Some(2),
+            port: 14,
+        };
+        assert_eq!(port.to_string(), "2/14");
+        assert_eq!(format!("{:>2}", SwitchPort::from(3)), " 3");
+    }
+
+    #[test]
+    fn test_get_ports_discovered() {
+        let mut switch = Switch::from_args(v2_args(), vec![]).unwrap();
+        switch.poe_ports.insert(1, vec![1, 2, 3, 5]);
+
+        switch.select_ports(Some("2-3,5".to_string()));
+        assert_eq!(switch.get_ports(), Ok(port_list(&[2, 3, 5])));
+
+        switch.select_ports(Some("3-5".to_string()));
+        assert_eq!(
+            switch.get_ports(),
+            Err("Port 4 is not a PoE port on core, expected one of: 1-3,5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_group_poe_ports() {
+        let entries = vec![
+            (vec![1, 2], SnmpValue::Integer(1)),
+            (vec![1, 1], SnmpValue::Integer(2)),
+            (vec![1, 4], SnmpValue::Other),
+            (vec![2, 7], SnmpValue::Integer(1)),
+        ];
+
+        let poe_ports = Switch::group_poe_ports(entries);
+        assert_eq!(poe_ports.get(&1), Some(&vec![1, 2]));
+        assert_eq!(poe_ports.get(&2), Some(&vec![7]));
+    }
+
+    #[test]
+    fn test_format_ports() {
+        assert_eq!(Switch::format_ports(&[1, 2, 3, 5, 7, 8]), "1-3,5,7-8");
+        assert_eq!(Switch::format_ports(&[4]), "4");
+        assert_eq!(Switch::format_ports(&[]), "");
+    }
 }
 
 #[cfg(test)]
 mod display_tests {
+    use super::tests::port_list;
     use super::*;
 
     // parse_ports edge cases
@@ -783,13 +2632,16 @@ mod display_tests {
 
     #[test]
     fn test_parse_ports_single_element_range() {
-        assert_eq!(Switch::parse_ports("5-5".to_string()), Ok(vec![5]));
+        assert_eq!(Switch::parse_ports("5-5".to_string()), Ok(port_list(&[5])));
     }
 
     #[test]
-    fn test_parse_ports_reversed_range_is_empty() {
-        // 6..=1 in Rust is an empty range — documents current behaviour
-        assert_eq!(Switch::parse_ports("6-1".to_string()), Ok(vec![]));
+    fn test_parse_ports_reversed_range() {
+        assert_eq!(
+            Switch::parse_ports("6-1".to_string()),
+            Err("Invalid port range: 6-1".to_string())
+        );
+        assert!(Switch::parse_ports("2:5-3".to_string()).is_err());
     }
 
     // SNMPVersion Display
@@ -814,6 +2666,98 @@ mod display_tests {
         assert_eq!(SNMPAuth::Sha512.to_string(), "SHA512");
     }
 
+    // SwitchRecord
+    #[test]
+    fn test_to_record_v2_omits_v3_settings() {
+        let switch = Switch {
+            name: "core".to_string(),
+            version: SNMPVersion::V2,
+            community: "private".to_string(),
+            ..Default::default()
+        };
+        let record = switch.to_record();
+        assert_eq!(record.version, "v2");
+        assert_eq!(record.username, None);
+        assert_eq!(record.auth, None);
+    }
+
+    #[test]
+    fn test_switch_result_display_power() {
+        colored::control::set_override(false);
+        let result = SwitchResult {
+            detection: Some("Delivering power".to_string()),
+            power_class: Some("Class 2".to_string()),
+            power_mw: Some(4200),
+            ..SwitchResult::new("core", 3, "On".to_string())
+        };
+
+        assert_eq!(
+            result.to_string(),
+            "Port:  3 - On, Delivering power, Class 2, 4.2 W (4200 mW)"
+        );
+    }
+
+    #[test]
+    fn test_switch_result_display_interface() {
+        colored::control::set_override(false);
+        let result = SwitchResult {
+            interface: Some("Gi1/0/3".to_string()),
+            description: Some("Lobby camera".to_string()),
+            link: Some("Up".to_string()),
+            speed_mbps: Some(1000),
+            ..SwitchResult::new("core", 3, "On".to_string())
+        };
+
+        assert_eq!(
+            result.to_string(),
+            "Port:  3 - On | Gi1/0/3 \"Lobby camera\", Up, 1000 Mb/s"
+        );
+    }
+
+    #[test]
+    fn test_switch_result_display_neighbor() {
+        colored::control::set_override(false);
+        let result = SwitchResult {
+            neighbor: Some("ap-lobby".to_string()),
+            neighbor_port: Some("eth0".to_string()),
+            neighbor_address: Some("10.0.0.21".to_string()),
+            ..SwitchResult::new("core", 1, "On".to_string())
+        };
+
+        assert_eq!(
+            result.to_string(),
+            "Port:  1 - On | Neighbor: ap-lobby port eth0 at 10.0.0.21"
+        );
+    }
+
+    #[test]
+    fn test_pse_status_display() {
+        let pse = PseStatus {
+            group: 1,
+            status: "On".to_string(),
+            power: Some(370),
+            consumption: Some(45),
+        };
+        assert_eq!(pse.to_string(), "PSE 1: On, 45 W of 370 W used (12%)");
+
+        let pse = PseStatus {
+            consumption: None,
+            ..pse
+        };
+        assert_eq!(pse.to_string(), "PSE 1: On, 370 W available");
+    }
+
+    #[test]
+    fn test_switch_result_error() {
+        let error = SnmpError::OperationError("timeout".to_string());
+        let result = SwitchResult::error("core", 3, &error);
+        assert_eq!(result.status, "Error");
+        assert_eq!(
+            result.error,
+            Some("SNMP operation failed: timeout".to_string())
+        );
+    }
+
     // SNMPEncryption Display
     #[test]
     fn test_snmp_encryption_display() {
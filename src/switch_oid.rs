@@ -1,8 +1,26 @@
+use crate::snmp::SnmpValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+#[derive(Clone, Debug)]
 pub struct SwitchOid {
     name: String,
     poe_oid: String,
     on: i64,
     off: i64,
+    // Names for admin state values other than on and off, or in place of On and Off
+    statuses: BTreeMap<i64, String>,
+    // Added to a port number to get its index in the table
+    port_offset: i64,
+    // The OID ends in a PSE group index, which is replaced for ports on other stack members
+    group_index: bool,
+    // Private enterprise numbers under 1.3.6.1.4.1 that the brand's sysObjectID uses
+    enterprises: Vec<u64>,
+    // Lowercase words that identify the brand in sysDescr
+    keywords: Vec<String>,
+    // Vendor column with the power each port draws in milliwatts, indexed by group and port
+    port_power_oid: Option<String>,
 }
 
 impl SwitchOid {
@@ -12,33 +30,239 @@ impl SwitchOid {
             poe_oid,
             on,
             off,
+            statuses: BTreeMap::new(),
+            port_offset: 0,
+            group_index: true,
+            enterprises: vec![],
+            keywords: vec![],
+            port_power_oid: None,
+        }
+    }
+
+    fn with_vendor(mut self, enterprises: &[u64], keywords: &[&str]) -> SwitchOid {
+        self.enterprises = enterprises.to_vec();
+        self.keywords = keywords.iter().map(|keyword| keyword.to_string()).collect();
+        self
+    }
+
+    fn with_port_power(mut self, port_power_oid: &str) -> SwitchOid {
+        self.port_power_oid = Some(port_power_oid.to_string());
+        self
+    }
+
+    // The table index of a port, None when the offset takes it below zero
+    pub fn port_index(&self, port: u64) -> Option<u64> {
+        port.checked_add_signed(self.port_offset)
+    }
+
+    // The group the OID addresses when no other stack member is asked for
+    pub fn get_default_group(&self) -> u64 {
+        match parse_oid(&self.poe_oid) {
+            Ok(oid) if self.group_index => oid.last().copied().unwrap_or(1),
+            _ => 1,
+        }
+    }
+
+    pub fn get_port_oid(&self, group: Option<u64>, port: u64) -> Result<Vec<u64>, String> {
+        let mut oid = parse_oid(&self.poe_oid)?;
+
+        if let Some(group) = group.filter(|group| *group != self.get_default_group()) {
+            if !self.group_index {
+                return Err(format!(
+                    "Brand {} cannot address stack member {}",
+                    self.name, group
+                ));
+            }
+            if let Some(last) = oid.last_mut() {
+                *last = group;
+            }
+        }
+
+        let index = self.port_index(port).ok_or_else(|| {
+            format!(
+                "Port {} has no index, check the port offset of brand {}",
+                port, self.name
+            )
+        })?;
+        oid.push(index);
+
+        Ok(oid)
+    }
+
+    pub fn get_status(&self, value: &SnmpValue) -> String {
+        match value {
+            SnmpValue::Integer(value) => match self.statuses.get(value) {
+                Some(status) => status.clone(),
+                None if *value == self.on => "On".to_string(),
+                None if *value == self.off => "Off".to_string(),
+                None => "Unknown".to_string(),
+            },
+            _ => "Unknown".to_string(),
+        }
+    }
+}
+
+impl From<&BrandProfile> for SwitchOid {
+    fn from(profile: &BrandProfile) -> Self {
+        SwitchOid {
+            statuses: profile.status.clone(),
+            port_offset: profile.port_offset,
+            group_index: profile.group_index,
+            enterprises: profile.enterprises.clone(),
+            ..SwitchOid::new(
+                profile.name.clone(),
+                profile.oid.clone(),
+                profile.on,
+                profile.off,
+            )
+        }
+    }
+}
+
+// A brand declared in a [[profiles]] section of config.toml, for switches that need
+// a private MIB, another PSE group or other on and off values
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BrandProfile {
+    pub name: String,
+    // Admin state column, the port index is appended to it
+    pub oid: String,
+    #[serde(default = "default_on")]
+    pub on: i64,
+    #[serde(default = "default_off")]
+    pub off: i64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub status: BTreeMap<i64, String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub port_offset: i64,
+    // Set when the OID ends in a PSE group index, so stacked switches can be addressed
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub group_index: bool,
+    // Enterprise numbers used to detect the brand from sysObjectID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enterprises: Vec<u64>,
+}
+
+fn default_on() -> i64 {
+    1
+}
+
+fn default_off() -> i64 {
+    2
+}
+
+fn is_zero(value: &i64) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl BrandProfile {
+    // Checked against the built-in brands and the profiles before it in the config file
+    pub fn validate(&self, known_names: &[String]) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Name is required".to_string());
+        }
+
+        if known_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&self.name))
+        {
+            return Err(format!("Brand {} already exists", self.name));
+        }
+
+        parse_oid(&self.oid)?;
+
+        if self.on == self.off {
+            return Err("On and off values must be different".to_string());
+        }
+
+        if let Some((value, _)) = self.status.iter().find(|(_, name)| name.trim().is_empty()) {
+            return Err(format!("Status name for value {} is empty", value));
         }
+
+        Ok(())
+    }
+}
+
+pub fn parse_oid(oid: &str) -> Result<Vec<u64>, String> {
+    let arcs = oid
+        .trim()
+        .trim_start_matches('.')
+        .split('.')
+        .map(|arc| arc.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| format!("Invalid OID: {}", oid))?;
+
+    // The first two arcs are encoded together, so they have limits of their own
+    if arcs.len() < 2 || arcs[0] > 2 || (arcs[0] < 2 && arcs[1] > 39) {
+        return Err(format!("Invalid OID: {}", oid));
     }
+
+    Ok(arcs)
 }
 
+// Profiles from config.toml, registered once the config file is read
+static PROFILES: OnceLock<Vec<BrandProfile>> = OnceLock::new();
+
+pub fn register_profiles(profiles: Vec<BrandProfile>) {
+    // The config file is read once per run, a second registration has nothing new
+    let _ = PROFILES.set(profiles);
+}
+
+// sysObjectID values start with this prefix followed by the enterprise number
+const ENTERPRISES_OID: [u64; 6] = [1, 3, 6, 1, 4, 1];
+
 pub struct SwitchOidBuilder {
-    switch_oids: [SwitchOid; 7],
+    switch_oids: Vec<SwitchOid>,
 }
 
 impl SwitchOidBuilder {
     pub fn new() -> Self {
+        SwitchOidBuilder::with_profiles(PROFILES.get().map(Vec::as_slice).unwrap_or_default())
+    }
+
+    // The built-in brands followed by the given profiles
+    pub fn with_profiles(profiles: &[BrandProfile]) -> Self {
         // Standard IEEE 802.3af PoE MIB OID: pethPsePortAdminEnable
         // 1 = enabled (on), 2 = disabled (off)
         let standard_poe_oid = "1.3.6.1.2.1.105.1.1.1.3.1";
 
-        let switch_oids = [
-            SwitchOid::new("Aruba".to_string(), standard_poe_oid.to_string(), 1, 2),
-            SwitchOid::new("Cisco".to_string(), standard_poe_oid.to_string(), 1, 2),
-            SwitchOid::new("Dell".to_string(), standard_poe_oid.to_string(), 1, 2),
-            SwitchOid::new("Juniper".to_string(), standard_poe_oid.to_string(), 1, 2),
-            SwitchOid::new("Netgear".to_string(), standard_poe_oid.to_string(), 1, 2),
-            SwitchOid::new("TP-Link".to_string(), standard_poe_oid.to_string(), 1, 2),
-            SwitchOid::new("Ubiquiti".to_string(), standard_poe_oid.to_string(), 1, 2),
+        let mut switch_oids = vec![
+            SwitchOid::new("Aruba".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[11, 14823, 47196], &["aruba", "procurve"]),
+            // CISCO-POWER-ETHERNET-EXT-MIB cpeExtPsePortPwrConsumption, column 9 of
+            // cpeExtPsePortEntry, which augments pethPsePortEntry. Gauge32 in milliwatts.
+            SwitchOid::new("Cisco".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[9], &["cisco"])
+                .with_port_power("1.3.6.1.4.1.9.9.402.1.2.1.9"),
+            SwitchOid::new("Dell".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[674, 6027], &["dell", "powerconnect"]),
+            SwitchOid::new("Juniper".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[2636], &["juniper", "junos"]),
+            // NETGEAR-POWER-ETHERNET-MIB agentPethOutputPower, column 2 of
+            // agentPethPsePortEntry, which augments pethPsePortEntry. Gauge32 in milliwatts.
+            SwitchOid::new("Netgear".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[4526], &["netgear"])
+                .with_port_power("1.3.6.1.4.1.4526.10.15.1.1.1.2"),
+            SwitchOid::new("TP-Link".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[11863], &["tp-link", "jetstream"]),
+            SwitchOid::new("Ubiquiti".to_string(), standard_poe_oid.to_string(), 1, 2)
+                .with_vendor(&[41112], &["ubiquiti", "unifi", "edgeswitch"]),
         ];
+        switch_oids.extend(profiles.iter().map(SwitchOid::from));
 
         Self { switch_oids }
     }
 
+    pub fn get_profile(&self, name: &str) -> Option<&SwitchOid> {
+        self.switch_oids
+            .iter()
+            .find(|switch_oid| switch_oid.name == name)
+    }
+
     pub fn get_oid_names(&self) -> Vec<String> {
         self.switch_oids
             .iter()
@@ -70,6 +294,42 @@ impl SwitchOidBuilder {
             .find(|switch_oid| switch_oid.name == name)
             .map(|switch_oid| switch_oid.off)
     }
+
+    pub fn get_port_power_oid(&self, name: &str) -> Option<&String> {
+        self.switch_oids
+            .iter()
+            .find(|switch_oid| switch_oid.name == name)
+            .and_then(|switch_oid| switch_oid.port_power_oid.as_ref())
+    }
+
+    // The enterprise number in sysObjectID is the most reliable hint, sysDescr is used
+    // for agents that report a chipset or OEM enterprise instead
+    pub fn detect_brand(&self, sys_object_id: &[u64], sys_descr: &str) -> Option<String> {
+        let enterprise = sys_object_id
+            .strip_prefix(ENTERPRISES_OID.as_slice())
+            .and_then(|arcs| arcs.first());
+
+        if let Some(enterprise) = enterprise {
+            if let Some(switch_oid) = self
+                .switch_oids
+                .iter()
+                .find(|switch_oid| switch_oid.enterprises.contains(enterprise))
+            {
+                return Some(switch_oid.name.clone());
+            }
+        }
+
+        let sys_descr = sys_descr.to_lowercase();
+        self.switch_oids
+            .iter()
+            .find(|switch_oid| {
+                switch_oid
+                    .keywords
+                    .iter()
+                    .any(|keyword| sys_descr.contains(keyword.as_str()))
+            })
+            .map(|switch_oid| switch_oid.name.clone())
+    }
 }
 
 #[cfg(test)]
@@ -127,4 +387,153 @@ mod tests {
     fn test_get_oid_name_by_index() {
         assert_eq!(SwitchOidBuilder::new().get_oid_name(0), "Aruba");
     }
+
+    fn profile() -> BrandProfile {
+        BrandProfile {
+            name: "Acme".to_string(),
+            oid: "1.3.6.1.4.1.99999.1.2".to_string(),
+            on: 3,
+            off: 4,
+            status: BTreeMap::from([(5, "Fault".to_string())]),
+            port_offset: 48,
+            group_index: false,
+            enterprises: vec![99999],
+        }
+    }
+
+    #[test]
+    fn test_with_profiles_merges_built_ins() {
+        let builder = SwitchOidBuilder::with_profiles(&[profile()]);
+        let names = builder.get_oid_names();
+        assert_eq!(names.len(), 8);
+        assert_eq!(names[7], "Acme");
+        assert_eq!(builder.get_on("Acme"), Some(3));
+        assert_eq!(
+            builder.detect_brand(&[1, 3, 6, 1, 4, 1, 99999, 1], ""),
+            Some("Acme".to_string())
+        );
+    }
+
+    #[test]
+    fn test_profile_port_oid_and_status() {
+        let builder = SwitchOidBuilder::with_profiles(&[profile()]);
+        let acme = builder.get_profile("Acme").unwrap();
+
+        assert_eq!(
+            acme.get_port_oid(None, 2),
+            Ok(vec![1, 3, 6, 1, 4, 1, 99999, 1, 2, 50])
+        );
+        assert!(acme.get_port_oid(Some(2), 2).is_err());
+        assert_eq!(acme.get_status(&SnmpValue::Integer(3)), "On");
+        assert_eq!(acme.get_status(&SnmpValue::Integer(4)), "Off");
+        assert_eq!(acme.get_status(&SnmpValue::Integer(5)), "Fault");
+        assert_eq!(acme.get_status(&SnmpValue::Integer(1)), "Unknown");
+    }
+
+    #[test]
+    fn test_port_oid_stack_member() {
+        let builder = SwitchOidBuilder::with_profiles(&[]);
+        let cisco = builder.get_profile("Cisco").unwrap();
+
+        assert_eq!(cisco.get_default_group(), 1);
+        assert_eq!(
+            cisco.get_port_oid(Some(2), 14),
+            Ok(vec![1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 3, 2, 14])
+        );
+        assert_eq!(
+            cisco.get_port_oid(None, 14),
+            Ok(vec![1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 3, 1, 14])
+        );
+    }
+
+    #[test]
+    fn test_built_in_status() {
+        let builder = SwitchOidBuilder::with_profiles(&[]);
+        let netgear = builder.get_profile("Netgear").unwrap();
+
+        assert_eq!(netgear.get_status(&SnmpValue::Integer(1)), "On");
+        assert_eq!(netgear.get_status(&SnmpValue::Integer(2)), "Off");
+        assert_eq!(netgear.get_status(&SnmpValue::Integer(0)), "Unknown");
+        assert_eq!(netgear.get_status(&SnmpValue::Other), "Unknown");
+    }
+
+    #[test]
+    fn test_profile_validate() {
+        let names = SwitchOidBuilder::with_profiles(&[]).get_oid_names();
+        assert!(profile().validate(&names).is_ok());
+
+        let duplicate = BrandProfile {
+            name: "cisco".to_string(),
+            ..profile()
+        };
+        assert_eq!(
+            duplicate.validate(&names),
+            Err("Brand cisco already exists".to_string())
+        );
+
+        let bad_oid = BrandProfile {
+            oid: "1.3.x".to_string(),
+            ..profile()
+        };
+        assert_eq!(
+            bad_oid.validate(&names),
+            Err("Invalid OID: 1.3.x".to_string())
+        );
+
+        let same_values = BrandProfile {
+            off: 3,
+            ..profile()
+        };
+        assert!(same_values.validate(&names).is_err());
+    }
+
+    #[test]
+    fn test_profile_from_toml() {
+        let profile: BrandProfile = toml::from_str(
+            "name = \"Acme\"\noid = \"1.3.6.1.4.1.99999.1.2\"\n[status]\n3 = \"Fault\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(profile.on, 1);
+        assert_eq!(profile.off, 2);
+        assert_eq!(profile.status.get(&3), Some(&"Fault".to_string()));
+    }
+
+    #[test]
+    fn test_get_port_power_oid() {
+        let builder = SwitchOidBuilder::new();
+        assert_eq!(
+            builder.get_port_power_oid("Cisco"),
+            Some(&"1.3.6.1.4.1.9.9.402.1.2.1.9".to_string())
+        );
+        assert_eq!(
+            builder.get_port_power_oid("Netgear"),
+            Some(&"1.3.6.1.4.1.4526.10.15.1.1.1.2".to_string())
+        );
+        assert_eq!(builder.get_port_power_oid("Aruba"), None);
+        assert_eq!(builder.get_port_power_oid("TP-Link"), None);
+    }
+
+    #[test]
+    fn test_detect_brand_from_enterprise() {
+        let builder = SwitchOidBuilder::new();
+        let brand = builder.detect_brand(&[1, 3, 6, 1, 4, 1, 9, 1, 2593], "");
+        assert_eq!(brand, Some("Cisco".to_string()));
+    }
+
+    #[test]
+    fn test_detect_brand_from_sys_descr() {
+        let builder = SwitchOidBuilder::new();
+        let brand = builder.detect_brand(&[1, 3, 6, 1, 4, 1, 4413], "EdgeSwitch 24-Port PoE");
+        assert_eq!(brand, Some("Ubiquiti".to_string()));
+    }
+
+    #[test]
+    fn test_detect_brand_unknown() {
+        let builder = SwitchOidBuilder::new();
+        assert_eq!(
+            builder.detect_brand(&[1, 3, 6, 1, 4, 1, 8072], "Linux"),
+            None
+        );
+    }
 }
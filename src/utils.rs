@@ -1,34 +1,109 @@
-use snmp2::Value;
+use crate::snmp::SnmpValue;
+use std::io::IsTerminal;
 
-pub fn get_status(status: Value) -> String {
+// pethPsePortDetectionStatus
+pub fn get_detection_status(status: &SnmpValue) -> String {
     match status {
-        Value::Integer(1) => "On".to_string(),
-        Value::Integer(2) => "Off".to_string(),
+        SnmpValue::Integer(1) => "Disabled",
+        SnmpValue::Integer(2) => "Searching",
+        SnmpValue::Integer(3) => "Delivering power",
+        SnmpValue::Integer(4) => "Fault",
+        SnmpValue::Integer(5) => "Test",
+        SnmpValue::Integer(6) => "Other fault",
+        _ => "Unknown",
+    }
+    .to_string()
+}
+
+// pethPsePortPowerClassifications, 1 is class0 through 5 for class4
+pub fn get_power_class(class: &SnmpValue) -> String {
+    match class {
+        SnmpValue::Integer(class @ 1..=5) => format!("Class {}", class - 1),
         _ => "Unknown".to_string(),
     }
 }
 
+// pethMainPseOperStatus
+pub fn get_pse_status(status: &SnmpValue) -> String {
+    match status {
+        SnmpValue::Integer(1) => "On",
+        SnmpValue::Integer(2) => "Off",
+        SnmpValue::Integer(3) => "Faulty",
+        _ => "Unknown",
+    }
+    .to_string()
+}
+
+// ifOperStatus
+pub fn get_oper_status(status: &SnmpValue) -> String {
+    match status {
+        SnmpValue::Integer(1) => "Up",
+        SnmpValue::Integer(2) => "Down",
+        SnmpValue::Integer(3) => "Testing",
+        SnmpValue::Integer(5) => "Dormant",
+        SnmpValue::Integer(6) => "Not present",
+        SnmpValue::Integer(7) => "Lower layer down",
+        _ => "Unknown",
+    }
+    .to_string()
+}
+
+// A DisplayString such as ifName or lldpRemSysName, empty strings and other types are None
+pub fn get_text(value: &SnmpValue) -> Option<String> {
+    match value {
+        SnmpValue::OctetString(_) => {
+            Some(value.to_string().trim().to_string()).filter(|text| !text.is_empty())
+        }
+        _ => None,
+    }
+}
+
+// Prompts are only shown when a user is at the keyboard
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_get_status_on() {
-        assert_eq!(get_status(Value::Integer(1)), "On");
+    fn test_get_detection_status() {
+        assert_eq!(
+            get_detection_status(&SnmpValue::Integer(3)),
+            "Delivering power"
+        );
+        assert_eq!(get_detection_status(&SnmpValue::Integer(4)), "Fault");
+        assert_eq!(get_detection_status(&SnmpValue::Other), "Unknown");
+    }
+
+    #[test]
+    fn test_get_power_class() {
+        assert_eq!(get_power_class(&SnmpValue::Integer(1)), "Class 0");
+        assert_eq!(get_power_class(&SnmpValue::Integer(5)), "Class 4");
+        assert_eq!(get_power_class(&SnmpValue::Integer(6)), "Unknown");
     }
 
     #[test]
-    fn test_get_status_off() {
-        assert_eq!(get_status(Value::Integer(2)), "Off");
+    fn test_get_text() {
+        assert_eq!(
+            get_text(&SnmpValue::OctetString(b" Gi1/0/3 ".to_vec())),
+            Some("Gi1/0/3".to_string())
+        );
+        assert_eq!(get_text(&SnmpValue::OctetString(Vec::new())), None);
+        assert_eq!(get_text(&SnmpValue::Integer(3)), None);
     }
 
     #[test]
-    fn test_get_status_unknown_value() {
-        assert_eq!(get_status(Value::Integer(3)), "Unknown");
+    fn test_get_pse_status() {
+        assert_eq!(get_pse_status(&SnmpValue::Integer(3)), "Faulty");
+        assert_eq!(get_pse_status(&SnmpValue::Other), "Unknown");
     }
 
     #[test]
-    fn test_get_status_zero() {
-        assert_eq!(get_status(Value::Integer(0)), "Unknown");
+    fn test_get_oper_status() {
+        assert_eq!(get_oper_status(&SnmpValue::Integer(1)), "Up");
+        assert_eq!(get_oper_status(&SnmpValue::Integer(7)), "Lower layer down");
+        assert_eq!(get_oper_status(&SnmpValue::Integer(4)), "Unknown");
     }
 }
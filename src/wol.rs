@@ -1,89 +1,273 @@
+use crate::arp::{self, NeighborEntry};
+use crate::bridge::MacLocation;
 use crate::device::Device;
-use regex::Regex;
+use crate::keyring;
+use crate::mac::MacAddress;
+use crate::probe::{self, ProbeType};
+use crate::shutdown::{self, Shutdown};
+use clap::Args;
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::{
-    net::{Ipv4Addr, UdpSocket},
-    num::ParseIntError,
-    sync::LazyLock,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
 };
 
-static MAC_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^([0-9A-Fa-f]{2}[:]){5}([0-9A-Fa-f]{2})$").unwrap()
-});
-
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Wol {
     pub name: String,
+    // Kept as written so a device with a bad address still loads, it is checked when used
     mac: String,
+    // Switch port the device was found on by locate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<WolLocation>,
+    #[serde(default, skip_serializing_if = "WolSettings::is_empty")]
+    network: WolSettings,
+    #[serde(skip)]
+    network_defaults: WolSettings,
+    // The SecureOn password itself is kept in the system keyring
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    secure_on: bool,
+    #[serde(skip)]
+    password: String,
+    // Host checked to see if the device woke up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(default, skip_serializing_if = "ProbeType::is_default")]
+    probe: ProbeType,
+    #[serde(
+        default = "probe::default_port",
+        skip_serializing_if = "probe::is_default_port"
+    )]
+    probe_port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shutdown: Option<Shutdown>,
+    #[serde(skip)]
+    wait: Duration,
+    #[serde(skip)]
+    resend_interval: Duration,
+}
+
+// Where magic packets are sent to and from, set per device or globally in the [wol] section
+// of config.toml. Unset device values fall back to the global ones and then to a broadcast
+// to 255.255.255.255 on port 9 from any interface.
+#[derive(Args, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WolSettings {
+    /// Broadcast address to send to, ex: 10.0.20.255 for another VLAN [default: 255.255.255.255]
+    #[clap(long, value_name = "IP")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast: Option<Ipv4Addr>,
+    /// UDP port to send to, usually 7 or 9 [default: 9]
+    #[clap(long = "udp-port", value_name = "PORT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Send to this address instead of broadcasting
+    #[clap(long, value_name = "IP")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unicast: Option<IpAddr>,
+    /// Network interface to send from, ex: eth1 (Linux only)
+    #[clap(long, value_name = "NAME")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Local address to send from
+    #[clap(long, value_name = "IP")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpAddr>,
+}
+
+impl WolSettings {
+    // Values set here win over the given defaults
+    pub fn or(&self, defaults: &WolSettings) -> WolSettings {
+        WolSettings {
+            broadcast: self.broadcast.or(defaults.broadcast),
+            port: self.port.or(defaults.port),
+            unicast: self.unicast.or(defaults.unicast),
+            interface: self.interface.clone().or(defaults.interface.clone()),
+            source: self.source.or(defaults.source),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == WolSettings::default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == Some(0) {
+            return Err("UDP port must be between 1 and 65535".to_string());
+        }
+        if self
+            .interface
+            .as_ref()
+            .is_some_and(|interface| interface.trim().is_empty())
+        {
+            return Err("Interface cannot be empty".to_string());
+        }
+        if let (Some(source), destination) = (self.source, self.get_destination()) {
+            if source.is_ipv4() != destination.is_ipv4() {
+                return Err(format!(
+                    "Source {} and destination {} are not the same IP version",
+                    source,
+                    destination.ip()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_destination(&self) -> SocketAddr {
+        let ip = self
+            .unicast
+            .unwrap_or(IpAddr::V4(self.broadcast.unwrap_or(Ipv4Addr::BROADCAST)));
+
+        SocketAddr::new(ip, self.port.unwrap_or(9))
+    }
+
+    // Binds to the source address, or any address of the destination's IP version
+    fn bind(&self) -> std::io::Result<UdpSocket> {
+        let destination = self.get_destination();
+        let source = self.source.unwrap_or(match destination {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        });
+
+        let socket = Socket::new(
+            Domain::for_address(destination),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+        if let Some(interface) = &self.interface {
+            bind_device(&socket, interface)?;
+        }
+        socket.bind(&SocketAddr::new(source, 0).into())?;
+        socket.set_broadcast(self.unicast.is_none())?;
+
+        Ok(socket.into())
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &Socket, interface: &str) -> std::io::Result<()> {
+    socket
+        .bind_device(Some(interface.as_bytes()))
+        .map_err(|e| std::io::Error::new(e.kind(), format!("Interface {}: {}", interface, e)))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_device(_socket: &Socket, _interface: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Sending from an interface is only supported on Linux, set a source address instead",
+    ))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WolLocation {
+    pub switch: String,
+    pub port: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<u64>,
 }
 
 impl Device for Wol {
     async fn disable(&mut self) -> std::io::Result<()> {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Disable not implemented for Wol",
-        ))
+        let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+
+        match &self.shutdown {
+            None => Err(invalid(format!(
+                "{} has no shutdown method, set one with wol update",
+                self.name
+            ))),
+            Some(Shutdown::Ssh {
+                user,
+                key,
+                port,
+                command,
+            }) => {
+                let host = self.host.as_ref().ok_or_else(|| {
+                    invalid(format!(
+                        "{} has no host to run the shutdown command on",
+                        self.name
+                    ))
+                })?;
+
+                let args = shutdown::ssh_args(host, user.as_deref(), key.as_ref(), *port, command);
+                shutdown::run_ssh(host, args)
+                    .await
+                    .map_err(std::io::Error::other)?;
+
+                println!("Ran shutdown command on {} ({})", self.name, host);
+                Ok(())
+            }
+            Some(Shutdown::SleepOnLan { port }) => {
+                let mac = self.get_mac().map_err(invalid)?;
+                let settings = self.get_settings();
+                let settings = WolSettings {
+                    port: port.or(settings.port),
+                    ..settings
+                };
+
+                let destination =
+                    send_magic_packet(&shutdown::sleep_on_lan_mac(&mac.octets()), &[], &settings)?;
+
+                println!(
+                    "Sent sleep-on-LAN packet to {} ({})",
+                    self.name, destination
+                );
+                Ok(())
+            }
+        }
     }
 
     fn update(&mut self) {
         let mac = dialoguer::Input::<String>::new()
             .with_prompt("MAC")
-            .validate_with(|input: &String| -> Result<(), &str> {
-                if !MAC_RE.is_match(input) {
-                    Err("Invalid MAC address")
-                } else {
-                    Ok(())
-                }
-            })
+            .validate_with(|input: &String| MacAddress::parse_unicast(input).map(|_| ()))
             .default(self.mac.clone())
             .interact()
             .unwrap();
 
-        self.mac = mac;
+        self.mac = MacAddress::parse_unicast(&mac).unwrap().to_string();
+        self.prompt_password();
+        self.prompt_probe();
+        self.prompt_shutdown();
     }
 
     async fn enable(&mut self) -> std::io::Result<()> {
-        // Create magic packet
-        // 6 bytes of 0xff followed by 16 repetitions of the target MAC address
-        let mut magic_packet = vec![0xff; 6];
-        let mac = match self.get_octets() {
-            Ok(m) => m.repeat(16),
-            Err(e) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Invalid MAC address: {}", e),
-                ))
-            }
+        let mac = match self.get_mac() {
+            Ok(m) => m.octets(),
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)),
         };
 
-        // build magic packet
-        magic_packet.extend(mac);
+        let password = self
+            .get_password()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
 
-        if magic_packet.len() != 102 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Magic packet is not 102 bytes",
-            ));
-        }
+        let settings = self.get_settings();
+        let destination = send_magic_packet(&mac, &password, &settings)?;
 
-        // Send magic packet to broadcast address on port 9
-        // Port 9 is the default port for Wake-on-Lan
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.set_broadcast(true)?;
+        println!("Sent Wake-on-Lan packet to {} ({})", self.name, destination);
 
-        socket.send_to(&magic_packet, (Ipv4Addr::new(255, 255, 255, 255), 9))?;
-
-        println!("Sent Wake-on-Lan packet to {}", self.name);
-        Ok(())
+        if self.wait.is_zero() {
+            return Ok(());
+        }
+        self.wait_until_awake(&mac, &password, &settings).await
     }
 
     async fn status(&mut self) {
-        println!("Not implemented");
+        let probe = self.probe_host().await;
+        let neighbors = arp::neighbor_table().unwrap_or_else(|e| {
+            println!("{}", e);
+            vec![]
+        });
+
+        println!("{}", self.to_status(probe, &neighbors));
     }
 }
 
 impl Wol {
-    pub fn create(wol_names: Vec<String>) -> Self {
+    pub fn create(wol_names: Vec<String>, network: WolSettings) -> Self {
         let name = dialoguer::Input::<String>::new()
             .with_prompt("Name")
             .validate_with(|input: &String| -> Result<(), &str> {
@@ -97,71 +281,867 @@ impl Wol {
             .unwrap();
 
         let mac = dialoguer::Input::<String>::new()
-            .with_prompt("MAC")
-            .validate_with(|input: &String| -> Result<(), &str> {
-                if !MAC_RE.is_match(input) {
-                    Err("Invalid MAC address. Format should be XX:XX:XX:XX:XX:XX where X is a hexadecimal digit")
-                } else {
+            .with_prompt(
+                "MAC (AA:BB:CC:DD:EE:FF, AA-BB-CC-DD-EE-FF, AABB.CCDD.EEFF or AABBCCDDEEFF)",
+            )
+            .validate_with(|input: &String| MacAddress::parse_unicast(input).map(|_| ()))
+            .interact()
+            .unwrap();
+        let mac = MacAddress::parse_unicast(&mac).unwrap().to_string();
+
+        let mut wol = Self {
+            name,
+            mac,
+            location: None,
+            network,
+            network_defaults: WolSettings::default(),
+            secure_on: false,
+            password: String::new(),
+            host: None,
+            probe: ProbeType::default(),
+            probe_port: probe::default_port(),
+            shutdown: None,
+            wait: Duration::ZERO,
+            resend_interval: Duration::ZERO,
+        };
+        wol.prompt_password();
+        wol.prompt_probe();
+        wol.prompt_shutdown();
+
+        wol
+    }
+
+    // Asks for the host that answers once the device is awake, and how to check it
+    fn prompt_probe(&mut self) {
+        let host = dialoguer::Input::<String>::new()
+            .with_prompt("Host to check once woken (empty for none)")
+            .default(self.host.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact()
+            .unwrap();
+        self.host = (!host.trim().is_empty()).then(|| host.trim().to_string());
+
+        if self.host.is_none() {
+            return;
+        }
+
+        let probe_types = [ProbeType::Tcp, ProbeType::Icmp];
+        let probe_index = dialoguer::Select::new()
+            .with_prompt("Check the host with")
+            .default(if self.probe == ProbeType::Tcp { 0 } else { 1 })
+            .items(probe_types)
+            .interact()
+            .unwrap();
+        self.probe = probe_types[probe_index];
+
+        if self.probe == ProbeType::Tcp {
+            self.probe_port = dialoguer::Input::<u16>::new()
+                .with_prompt("TCP port")
+                .default(self.probe_port)
+                .interact()
+                .unwrap();
+        }
+    }
+
+    // Checks the device's host, None when it has no host to check
+    pub async fn probe_host(&self) -> Option<Result<Duration, String>> {
+        match &self.host {
+            Some(host) => Some(probe::probe(host, self.probe, self.probe_port).await),
+            None => None,
+        }
+    }
+
+    // The probe result and where the local host has seen the device's MAC address. Probing
+    // first fills in the neighbor table for devices on the same LAN.
+    pub fn to_status(
+        &self,
+        probe: Option<Result<Duration, String>>,
+        neighbors: &[NeighborEntry],
+    ) -> WolStatus {
+        let neighbor = self
+            .get_mac()
+            .ok()
+            .and_then(|mac| arp::find_mac(neighbors, &mac.octets()));
+
+        WolStatus {
+            name: self.name.clone(),
+            mac: self.mac.clone(),
+            host: self.host.clone(),
+            up: probe.as_ref().map(Result::is_ok),
+            latency_ms: probe
+                .as_ref()
+                .and_then(|probe| probe.as_ref().ok())
+                .map(|latency| latency.as_millis()),
+            lan_address: neighbor.map(|neighbor| neighbor.ip),
+            lan_state: neighbor.map(|neighbor| neighbor.state.clone()),
+            error: probe.and_then(Result::err),
+            target: self
+                .host
+                .as_ref()
+                .map(|host| self.probe.describe(host, self.probe_port)),
+        }
+    }
+
+    // Asks how disable shuts the device down. The SSH command runs on the host asked for
+    // by prompt_probe.
+    fn prompt_shutdown(&mut self) {
+        let methods = ["None", "SSH command", "Sleep-on-LAN packet"];
+        let method = dialoguer::Select::new()
+            .with_prompt("Shutdown method for disable")
+            .default(match self.shutdown {
+                None => 0,
+                Some(Shutdown::Ssh { .. }) => 1,
+                Some(Shutdown::SleepOnLan { .. }) => 2,
+            })
+            .items(methods)
+            .interact()
+            .unwrap();
+
+        self.shutdown = match (method, self.shutdown.take()) {
+            (1, current) => {
+                let (user, key, port, command) = match current {
+                    Some(Shutdown::Ssh {
+                        user,
+                        key,
+                        port,
+                        command,
+                    }) => (user, key, port, command),
+                    _ => (None, None, None, shutdown::default_command()),
+                };
+
+                if self.host.is_none() {
+                    println!("The SSH command needs a host, set one to check once woken");
+                }
+
+                let user = dialoguer::Input::<String>::new()
+                    .with_prompt("SSH user (empty for ssh's default)")
+                    .default(user.unwrap_or_default())
+                    .allow_empty(true)
+                    .interact()
+                    .unwrap();
+                let key = dialoguer::Input::<String>::new()
+                    .with_prompt("SSH private key file (empty for ssh's default keys)")
+                    .default(key.map(|key| key.display().to_string()).unwrap_or_default())
+                    .allow_empty(true)
+                    .interact()
+                    .unwrap();
+                let port = dialoguer::Input::<String>::new()
+                    .with_prompt("SSH port (empty for ssh's default)")
+                    .default(port.map(|port| port.to_string()).unwrap_or_default())
+                    .allow_empty(true)
+                    .validate_with(|input: &String| match input.trim() {
+                        "" => Ok(()),
+                        port => port
+                            .parse::<u16>()
+                            .map(|_| ())
+                            .map_err(|_| format!("Invalid port: {}", port)),
+                    })
+                    .interact()
+                    .unwrap();
+                let command = dialoguer::Input::<String>::new()
+                    .with_prompt("Shutdown command")
+                    .default(command)
+                    .interact()
+                    .unwrap();
+
+                Some(Shutdown::Ssh {
+                    user: (!user.trim().is_empty()).then(|| user.trim().to_string()),
+                    key: (!key.trim().is_empty()).then(|| key.trim().into()),
+                    port: port.trim().parse().ok(),
+                    command,
+                })
+            }
+            (2, Some(Shutdown::SleepOnLan { port })) => Some(Shutdown::SleepOnLan { port }),
+            (2, _) => Some(Shutdown::SleepOnLan { port: None }),
+            _ => None,
+        };
+    }
+
+    // Waiting for the device to wake up is up to timeout, zero to not wait, resending the
+    // magic packet every interval
+    pub(crate) fn select_wait(&mut self, timeout: Duration, resend_interval: Duration) {
+        self.wait = timeout;
+        self.resend_interval = resend_interval;
+    }
+
+    // Probes the host until it answers, resending the magic packet in case the first ones
+    // were lost or came too early
+    async fn wait_until_awake(
+        &self,
+        mac: &[u8],
+        password: &[u8],
+        settings: &WolSettings,
+    ) -> std::io::Result<()> {
+        let Some(host) = &self.host else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} has no host to check, set one with wol update",
+                    self.name
+                ),
+            ));
+        };
+
+        println!(
+            "Waiting up to {}s for {}",
+            self.wait.as_secs(),
+            self.probe.describe(host, self.probe_port)
+        );
+
+        // Probes for one resend interval at a time, resending the packet in between
+        let start = Instant::now();
+        loop {
+            let window = self
+                .wait
+                .saturating_sub(start.elapsed())
+                .min(self.resend_interval);
+            match probe::wait_until_up(host, self.probe, self.probe_port, window).await {
+                Ok(_) => {
+                    println!(
+                        "{} is awake after {}s",
+                        self.name,
+                        start.elapsed().as_secs()
+                    );
+                    return Ok(());
+                }
+                Err(e) if start.elapsed() + probe::PROBE_INTERVAL >= self.wait => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!(
+                            "{} did not wake up within {}s: {}",
+                            self.name,
+                            self.wait.as_secs(),
+                            e
+                        ),
+                    ));
+                }
+                Err(_) => tokio::time::sleep(probe::PROBE_INTERVAL).await,
+            }
+
+            let destination = send_magic_packet(mac, password, settings)?;
+            println!(
+                "Resent Wake-on-Lan packet to {} ({})",
+                self.name, destination
+            );
+        }
+    }
+
+    // Asks whether the device needs a SecureOn password, and for the password. When
+    // updating, an empty password keeps the one in the keyring.
+    fn prompt_password(&mut self) {
+        let secure_on = dialoguer::Confirm::new()
+            .with_prompt("Does the device need a SecureOn password?")
+            .default(self.secure_on)
+            .interact()
+            .unwrap();
+
+        if !secure_on {
+            if self.secure_on {
+                self.remove_keys();
+            }
+            self.secure_on = false;
+            self.password.clear();
+            return;
+        }
+
+        let prompt = if self.secure_on {
+            "SecureOn password (empty to keep the current one)"
+        } else {
+            "SecureOn password (XX:XX:XX:XX:XX:XX, XX:XX:XX:XX or A.B.C.D)"
+        };
+        let password = dialoguer::Password::new()
+            .with_prompt(prompt)
+            .allow_empty_password(self.secure_on)
+            .validate_with(|input: &String| -> Result<(), String> {
+                if input.is_empty() {
                     Ok(())
+                } else {
+                    parse_password(input).map(|_| ())
                 }
             })
             .interact()
             .unwrap();
 
-        Self { name, mac }
+        self.secure_on = true;
+        if !password.is_empty() {
+            self.password = password;
+        }
     }
 
-    pub fn get_octets(&self) -> Result<Vec<u8>, ParseIntError> {
-        let mut octets = Vec::<u8>::new();
-        for octet in self.mac.split(":") {
-            octets.push(u8::from_str_radix(octet, 16)?);
+    // The SecureOn password bytes, empty when the device does not use one
+    pub fn get_password(&self) -> Result<Vec<u8>, String> {
+        if !self.secure_on {
+            return Ok(vec![]);
         }
 
-        Ok(octets)
+        if self.password.is_empty() {
+            return Err(format!(
+                "SecureOn password for {} could not be loaded from the keyring",
+                self.name
+            ));
+        }
+
+        parse_password(&self.password)
+    }
+
+    pub fn get_settings(&self) -> WolSettings {
+        self.network.or(&self.network_defaults)
+    }
+
+    pub(crate) fn set_network_defaults(&mut self, defaults: WolSettings) {
+        self.network_defaults = defaults;
+    }
+
+    // Settings given on the command line replace the device's own. A reset clears the
+    // device's own first, leaving only the given ones.
+    pub(crate) fn apply_settings(&mut self, network: WolSettings, reset: bool) {
+        self.network = if reset {
+            network
+        } else {
+            network.or(&self.network)
+        };
+    }
+
+    // The device's MAC address, which must be a unicast one to be woken or located
+    pub fn get_mac(&self) -> Result<MacAddress, String> {
+        MacAddress::parse_unicast(&self.mac)
+    }
+
+    // Saves the switch port a device was located on, which needs a port it is mapped to
+    pub fn set_location(&mut self, location: &MacLocation) -> Result<(), String> {
+        let port = location.get_port().ok_or_else(|| {
+            format!(
+                "ifIndex {} on {} is not a switch port",
+                location.if_index, location.switch
+            )
+        })?;
+
+        self.location = Some(WolLocation {
+            switch: location.switch.clone(),
+            port: port.to_string(),
+            vlan: location.vlan,
+        });
+
+        Ok(())
+    }
+
+    //
+    // Key ring functions
+    //
+    pub(crate) fn remove_keys(&self) {
+        if self.secure_on {
+            keyring::remove_key(&self.name, keyring::KeyRingType::SecureOn).unwrap_or_else(|e| {
+                println!(
+                    "Error removing {} password for {}: {}",
+                    keyring::KeyRingType::SecureOn,
+                    self.name,
+                    e
+                )
+            });
+        }
+    }
+
+    pub(crate) fn get_keys(&mut self) {
+        if !self.secure_on {
+            return;
+        }
+
+        self.password = keyring::get_key(&self.name, keyring::KeyRingType::SecureOn)
+            .unwrap_or_else(|e| {
+                println!(
+                    "Warning: could not load {} password for {}: {}",
+                    keyring::KeyRingType::SecureOn,
+                    self.name,
+                    e
+                );
+                String::new()
+            });
+    }
+
+    pub(crate) fn set_keys(&self) {
+        if self.secure_on && !self.password.is_empty() {
+            keyring::set_key(&self.name, &self.password, keyring::KeyRingType::SecureOn)
+                .unwrap_or_else(|e| {
+                    println!(
+                        "Error storing {} password for {}: {}",
+                        keyring::KeyRingType::SecureOn,
+                        self.name,
+                        e
+                    )
+                });
+        }
+    }
+
+    pub fn to_record(&self) -> WolRecord {
+        let location = self.location.as_ref();
+        let settings = self.get_settings();
+
+        WolRecord {
+            name: self.name.clone(),
+            mac: self.mac.clone(),
+            switch: location.map(|location| location.switch.clone()),
+            port: location.map(|location| location.port.clone()),
+            vlan: location.and_then(|location| location.vlan),
+            sends_to: settings.get_destination().to_string(),
+            interface: settings.interface,
+            source: settings.source,
+            secure_on: self.secure_on,
+            host: self.host.clone(),
+            probe: self.host.as_ref().map(|_| self.probe),
+            probe_port: (self.host.is_some() && self.probe == ProbeType::Tcp)
+                .then_some(self.probe_port),
+            shutdown: self
+                .shutdown
+                .as_ref()
+                .map(|shutdown| shutdown.method().to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct WolRecord {
+    pub name: String,
+    pub mac: String,
+    pub switch: Option<String>,
+    pub port: Option<String>,
+    pub vlan: Option<u64>,
+    pub sends_to: String,
+    pub interface: Option<String>,
+    pub source: Option<IpAddr>,
+    pub secure_on: bool,
+    pub host: Option<String>,
+    pub probe: Option<ProbeType>,
+    pub probe_port: Option<u16>,
+    pub shutdown: Option<String>,
+}
+
+// Whether a device is up and seen on the LAN as shown by status
+#[derive(Serialize)]
+pub struct WolStatus {
+    pub name: String,
+    pub mac: String,
+    pub host: Option<String>,
+    pub up: Option<bool>,
+    pub latency_ms: Option<u128>,
+    pub lan_address: Option<IpAddr>,
+    pub lan_state: Option<String>,
+    pub error: Option<String>,
+    // The host and how it was checked
+    #[serde(skip)]
+    target: Option<String>,
+}
+
+impl std::fmt::Display for WolStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Status for {}:", self.name)?;
+
+        if let Some(target) = &self.target {
+            match (self.latency_ms, &self.error) {
+                (Some(latency), _) => writeln!(f, "\tHost: {} up, {} ms", target, latency)?,
+                (None, Some(e)) => writeln!(f, "\tHost: {} down, {}", target, e)?,
+                (None, None) => writeln!(f, "\tHost: {} down", target)?,
+            }
+        }
+
+        match (&self.lan_address, &self.lan_state) {
+            (Some(address), Some(state)) => {
+                write!(f, "\tLAN: {} at {} ({})", self.mac, address, state)
+            }
+            _ => write!(f, "\tLAN: {} not in the neighbor table", self.mac),
+        }
+    }
+}
+
+// Sends the magic packet as the settings say, returning where it was sent. A SecureOn
+// password, when given, is appended to the packet.
+pub fn send_magic_packet(
+    mac: &[u8],
+    password: &[u8],
+    settings: &WolSettings,
+) -> std::io::Result<SocketAddr> {
+    settings
+        .validate()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    // Create magic packet
+    // 6 bytes of 0xff followed by 16 repetitions of the target MAC address and the
+    // optional 4 or 6 byte SecureOn password
+    let mut magic_packet = vec![0xff; 6];
+
+    // build magic packet
+    magic_packet.extend(mac.repeat(16));
+    magic_packet.extend(password);
+
+    if ![102, 106, 108].contains(&magic_packet.len()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "Magic packet is {} bytes, expected 102, or 106 or 108 with a SecureOn password",
+                magic_packet.len()
+            ),
+        ));
+    }
+
+    // Send magic packet to the broadcast address on port 9 unless set otherwise
+    // Port 9 is the default port for Wake-on-Lan
+    let socket = settings.bind()?;
+    let destination = settings.get_destination();
+
+    socket.send_to(&magic_packet, destination)?;
+
+    Ok(destination)
+}
+
+// A SecureOn password written as 6 or 4 hex octets separated by colons or dashes, or as
+// 4 decimal octets like an IPv4 address
+pub fn parse_password(input: &str) -> Result<Vec<u8>, String> {
+    let invalid = || {
+        format!(
+            "Invalid SecureOn password, use XX:XX:XX:XX:XX:XX, XX:XX:XX:XX or A.B.C.D: {}",
+            input
+        )
+    };
+
+    if let Ok(ip) = input.parse::<Ipv4Addr>() {
+        return Ok(ip.octets().to_vec());
+    }
+
+    let octets = input
+        .split([':', '-'])
+        .map(|octet| match octet.len() {
+            2 => u8::from_str_radix(octet, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+
+    match octets.len() {
+        4 | 6 => Ok(octets),
+        _ => Err(invalid()),
     }
 }
 
 impl std::fmt::Display for Wol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "  Name: {}\n  MAC: {}\n", self.name, self.mac)
+        write!(f, "  Name: {}\n  MAC: {}\n", self.name, self.mac)?;
+
+        let settings = self.get_settings();
+        writeln!(f, "  Sends To: {}", settings.get_destination())?;
+        if let Some(interface) = &settings.interface {
+            writeln!(f, "  Interface: {}", interface)?;
+        }
+        if let Some(source) = settings.source {
+            writeln!(f, "  Source: {}", source)?;
+        }
+        if self.secure_on {
+            writeln!(f, "  SecureOn: password in keyring")?;
+        }
+        if let Some(host) = &self.host {
+            writeln!(f, "  Probe: {}", self.probe.describe(host, self.probe_port))?;
+        }
+        if let Some(shutdown) = &self.shutdown {
+            writeln!(
+                f,
+                "  Shutdown: {}",
+                shutdown.describe(self.host.as_deref(), settings.get_destination())
+            )?;
+        }
+
+        if let Some(location) = &self.location {
+            write!(f, "  Location: {} port {}", location.switch, location.port)?;
+            if let Some(vlan) = location.vlan {
+                write!(f, ", VLAN {}", vlan)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::probe::tests::UNREACHABLE_HOST;
 
-    fn wol(mac: &str) -> Wol {
-        Wol { name: "test".to_string(), mac: mac.to_string() }
+    pub(crate) fn wol(mac: &str) -> Wol {
+        Wol {
+            name: "test".to_string(),
+            mac: mac.to_string(),
+            location: None,
+            network: WolSettings::default(),
+            network_defaults: WolSettings::default(),
+            secure_on: false,
+            password: String::new(),
+            host: None,
+            probe: ProbeType::default(),
+            probe_port: probe::default_port(),
+            shutdown: None,
+            wait: Duration::ZERO,
+            resend_interval: Duration::ZERO,
+        }
     }
 
     #[test]
-    fn test_get_octets_valid() {
-        assert_eq!(wol("AA:BB:CC:DD:EE:FF").get_octets().unwrap(), vec![0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]);
+    fn test_set_location() {
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        assert_eq!(
+            device.get_mac().map(|mac| mac.octets()),
+            Ok([0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c])
+        );
+
+        let mut location = MacLocation {
+            switch: "core".to_string(),
+            group: Some(2),
+            port: Some(14),
+            if_index: 10214,
+            vlan: Some(10),
+            macs_on_port: 1,
+        };
+        device.set_location(&location).unwrap();
+        assert_eq!(
+            device.to_string(),
+            "  Name: test\n  MAC: 00:1B:21:AA:BB:0C\n  Sends To: 255.255.255.255:9\n  Location: core port 2/14, VLAN 10\n"
+        );
+        assert_eq!(device.to_record().port, Some("2/14".to_string()));
+
+        location.port = None;
+        assert!(device.set_location(&location).is_err());
+    }
+
+    #[test]
+    fn test_settings_fall_back_to_defaults() {
+        let device = WolSettings {
+            port: Some(7),
+            ..Default::default()
+        };
+        let defaults = WolSettings {
+            broadcast: Some(Ipv4Addr::new(10, 0, 20, 255)),
+            port: Some(9),
+            interface: Some("eth1".to_string()),
+            ..Default::default()
+        };
+
+        let settings = device.or(&defaults);
+        assert_eq!(settings.get_destination().to_string(), "10.0.20.255:7");
+        assert_eq!(settings.interface, Some("eth1".to_string()));
+        assert_eq!(
+            WolSettings::default().get_destination().to_string(),
+            "255.255.255.255:9"
+        );
     }
 
     #[test]
-    fn test_get_octets_lowercase() {
-        assert_eq!(wol("aa:bb:cc:dd:ee:ff").get_octets().unwrap(), vec![0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]);
+    fn test_apply_settings_reset() {
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        device.apply_settings(
+            WolSettings {
+                port: Some(7),
+                interface: Some("eth1".to_string()),
+                ..Default::default()
+            },
+            false,
+        );
+        device.apply_settings(
+            WolSettings {
+                port: Some(9),
+                ..Default::default()
+            },
+            false,
+        );
+        assert_eq!(device.network.interface, Some("eth1".to_string()));
+        assert_eq!(device.network.port, Some(9));
+
+        device.apply_settings(WolSettings::default(), true);
+        assert!(device.network.is_empty());
+    }
+
+    #[test]
+    fn test_settings_unicast_wins_over_broadcast() {
+        let settings = WolSettings {
+            broadcast: Some(Ipv4Addr::new(10, 0, 20, 255)),
+            unicast: Some("10.0.20.15".parse().unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(settings.get_destination().to_string(), "10.0.20.15:9");
     }
 
     #[test]
-    fn test_get_octets_zeros() {
-        assert_eq!(wol("00:00:00:00:00:00").get_octets().unwrap(), vec![0, 0, 0, 0, 0, 0]);
+    fn test_settings_validate() {
+        let settings = WolSettings {
+            port: Some(0),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let settings = WolSettings {
+            unicast: Some("2001:db8::15".parse().unwrap()),
+            source: Some("10.0.20.5".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
     }
 
     #[test]
-    fn test_get_octets_broadcast() {
-        assert_eq!(wol("FF:FF:FF:FF:FF:FF").get_octets().unwrap(), vec![0xFF; 6]);
+    fn test_send_magic_packet_to_listener() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let settings = WolSettings {
+            port: Some(listener.local_addr().unwrap().port()),
+            unicast: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            source: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ..Default::default()
+        };
+        let mac = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c];
+
+        send_magic_packet(&mac, &[], &settings).unwrap();
+
+        let mut packet = [0u8; 200];
+        let (length, _) = listener.recv_from(&mut packet).unwrap();
+        assert_eq!(length, 102);
+        assert_eq!(&packet[..6], &[0xff; 6]);
+        assert_eq!(&packet[96..102], &mac);
+
+        send_magic_packet(&mac, &[192, 168, 1, 10], &settings).unwrap();
+        let (length, _) = listener.recv_from(&mut packet).unwrap();
+        assert_eq!(length, 106);
+        assert_eq!(&packet[102..106], &[192, 168, 1, 10]);
+
+        assert!(send_magic_packet(&mac, &[1, 2, 3], &settings).is_err());
     }
 
     #[test]
-    fn test_get_octets_invalid_hex() {
-        assert!(wol("ZZ:BB:CC:DD:EE:FF").get_octets().is_err());
+    fn test_parse_password() {
+        assert_eq!(
+            parse_password("01:23:45:ab:CD:ef"),
+            Ok(vec![0x01, 0x23, 0x45, 0xab, 0xcd, 0xef])
+        );
+        assert_eq!(
+            parse_password("01-23-45-67"),
+            Ok(vec![0x01, 0x23, 0x45, 0x67])
+        );
+        assert_eq!(parse_password("192.168.1.10"), Ok(vec![192, 168, 1, 10]));
+        assert!(parse_password("01:23:45").is_err());
+        assert!(parse_password("01:23:45:67:89:ab:cd").is_err());
+        assert!(parse_password("0123:45:67").is_err());
     }
 
     #[test]
-    fn test_get_octets_length() {
-        assert_eq!(wol("01:23:45:67:89:AB").get_octets().unwrap().len(), 6);
+    fn test_get_password() {
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        assert_eq!(device.get_password(), Ok(vec![]));
+
+        device.secure_on = true;
+        assert!(device.get_password().is_err());
+
+        device.password = "01:23:45:67".to_string();
+        assert_eq!(device.get_password(), Ok(vec![0x01, 0x23, 0x45, 0x67]));
+        assert!(device
+            .to_string()
+            .contains("  SecureOn: password in keyring\n"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_awake() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let settings = WolSettings {
+            port: Some(listener.local_addr().unwrap().port()),
+            unicast: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ..Default::default()
+        };
+        let mac = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c];
+
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        device.select_wait(Duration::from_secs(1), Duration::ZERO);
+        assert!(device.wait_until_awake(&mac, &[], &settings).await.is_err());
+
+        // Nothing listens on the probed port, the reset is enough to know the host is up
+        device.host = Some("127.0.0.1".to_string());
+        device.probe_port = listener.local_addr().unwrap().port();
+        assert!(device.wait_until_awake(&mac, &[], &settings).await.is_ok());
+        assert_eq!(
+            device.to_string(),
+            format!(
+                "  Name: test\n  MAC: 00:1B:21:AA:BB:0C\n  Sends To: 255.255.255.255:9\n  Probe: 127.0.0.1:{}\n",
+                device.probe_port
+            )
+        );
+
+        device.host = Some(UNREACHABLE_HOST.to_string());
+        let error = device
+            .wait_until_awake(&mac, &[], &settings)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        let neighbors = vec![NeighborEntry {
+            ip: "10.0.20.5".parse().unwrap(),
+            mac: [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c],
+            state: "reachable".to_string(),
+        }];
+
+        let status = device.to_status(device.probe_host().await, &neighbors);
+        assert_eq!(status.up, None);
+        assert_eq!(
+            status.to_string(),
+            "Status for test:\n\tLAN: 00:1B:21:AA:BB:0C at 10.0.20.5 (reachable)"
+        );
+
+        device.host = Some(UNREACHABLE_HOST.to_string());
+        device.probe = ProbeType::Icmp;
+        let status = device.to_status(device.probe_host().await, &[]);
+        assert_eq!(status.up, Some(false));
+        assert!(status
+            .to_string()
+            .starts_with("Status for test:\n\tHost: 255.255.255.255 (ICMP echo) down, "));
+        assert!(status
+            .to_string()
+            .ends_with("\tLAN: 00:1B:21:AA:BB:0C not in the neighbor table"));
+    }
+
+    #[tokio::test]
+    async fn test_disable_sleep_on_lan() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        assert!(device.disable().await.is_err());
+
+        device.network = WolSettings {
+            unicast: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ..Default::default()
+        };
+        device.shutdown = Some(Shutdown::SleepOnLan {
+            port: Some(listener.local_addr().unwrap().port()),
+        });
+        device.disable().await.unwrap();
+
+        let mut packet = [0u8; 200];
+        let (length, _) = listener.recv_from(&mut packet).unwrap();
+        assert_eq!(length, 102);
+        assert_eq!(&packet[6..12], &[0x0c, 0xbb, 0xaa, 0x21, 0x1b, 0x00]);
+        assert!(device.to_string().ends_with(&format!(
+            "  Shutdown: sleep-on-LAN packet to 127.0.0.1:{}\n",
+            listener.local_addr().unwrap().port()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_disable_ssh_needs_host() {
+        let mut device = wol("00:1B:21:AA:BB:0C");
+        device.shutdown = Some(Shutdown::Ssh {
+            user: None,
+            key: None,
+            port: None,
+            command: shutdown::default_command(),
+        });
+
+        let error = device.disable().await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(device
+            .to_string()
+            .ends_with("  Shutdown: ssh no host set \"sudo shutdown -h now\"\n"));
     }
 }